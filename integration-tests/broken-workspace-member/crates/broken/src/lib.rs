@@ -0,0 +1,3 @@
+pub fn roll() -> u8 {
+    rand::random()
+}
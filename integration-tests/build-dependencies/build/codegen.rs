@@ -0,0 +1,10 @@
+include!("consts.rs");
+
+// Relative to the directory of this file, `build/`, even though this isn't a `mod.rs` file.
+#[path = "version.rs"]
+mod version;
+
+pub fn generate(greeting: &str) {
+    log_once::info_once!("{greeting} from the build script, version {VERSION}");
+    version::log_version();
+}
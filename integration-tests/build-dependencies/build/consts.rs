@@ -0,0 +1 @@
+const VERSION: u32 = 1;
@@ -0,0 +1,9 @@
+mod codegen;
+
+lazy_static::lazy_static! {
+    static ref GREETING: &'static str = "hello";
+}
+
+fn main() {
+    codegen::generate(*GREETING);
+}
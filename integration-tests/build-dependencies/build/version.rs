@@ -0,0 +1,3 @@
+pub fn log_version() {
+    log::info!("{}", super::VERSION);
+}
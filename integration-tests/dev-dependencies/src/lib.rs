@@ -0,0 +1,22 @@
+/// Logs a friendly message.
+///
+/// ```
+/// log_once::info_once!("[dev-dependencies] used in a doc-test");
+/// dev_dependencies::hello();
+/// ```
+pub fn hello() {
+    // serde::Serialize isn't used anywhere, this is a mere comment.
+    log::info!("[dependencies]");
+}
+
+#[cfg(test)]
+mod tests {
+    lazy_static::lazy_static! {
+        static ref ANSWER: u32 = 42;
+    }
+
+    #[test]
+    fn answer() {
+        assert_eq!(*ANSWER, 42);
+    }
+}
@@ -0,0 +1,6 @@
+#[test]
+fn ready() {
+    futures::executor::block_on(async {
+        dev_dependencies::hello();
+    });
+}
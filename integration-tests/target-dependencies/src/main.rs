@@ -0,0 +1,4 @@
+fn main() {
+    #[cfg(unix)]
+    log::info!("[target.'cfg(unix)'.dependencies]");
+}
@@ -0,0 +1,5 @@
+#[cfg(windows)]
+#[test]
+fn log_once() {
+    log_once::info_once!("[target.'cfg(windows)'.dev-dependencies]");
+}
@@ -0,0 +1,8 @@
+pub fn hello() {
+    log::info!("[dependencies]");
+}
+
+#[test]
+fn test_hello() {
+    log_once::info_once!("[dev-dependencies]");
+}
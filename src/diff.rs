@@ -0,0 +1,166 @@
+//! Unified diffs of the Cargo.toml files, to show what `--fix` would change without writing
+//! anything.
+
+use std::fmt::Write as _;
+
+/// Number of unchanged lines displayed around each change.
+const CONTEXT: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Computes the line operations turning `old` into `new`, from their longest common subsequence.
+///
+/// The LCS table takes O(n·m) time and memory, so the lines common to the start and the end of
+/// both texts are skipped first: edits are local (removed entries, inserted ignored names and
+/// tables), and the table then only covers the region between the first and the last change.
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<(Op, usize, usize)> {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let (n, m) = (old.len() - suffix, new.len() - suffix);
+
+    // Each operation comes with the index of the line in the old and the new text.
+    let mut ops = (0..prefix).map(|i| (Op::Equal, i, i)).collect::<Vec<_>>();
+
+    // `lcs[i][j]` is the length of the LCS of `old[prefix + i..n]` and `new[prefix + j..m]`.
+    let (rows, columns) = (n - prefix, m - prefix);
+    let mut lcs = vec![vec![0usize; columns + 1]; rows + 1];
+    for i in (0..rows).rev() {
+        for j in (0..columns).rev() {
+            lcs[i][j] = if old[prefix + i] == new[prefix + j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < rows || j < columns {
+        if i < rows && j < columns && old[prefix + i] == new[prefix + j] {
+            ops.push((Op::Equal, prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if i < rows && (j == columns || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push((Op::Delete, prefix + i, prefix + j));
+            i += 1;
+        } else {
+            ops.push((Op::Insert, prefix + i, prefix + j));
+            j += 1;
+        }
+    }
+
+    ops.extend((0..suffix).map(|k| (Op::Equal, n + k, m + k)));
+    ops
+}
+
+/// Formats the start and length of a hunk's range, as in `@@ -start,len +start,len @@`.
+fn range(start: usize, len: usize) -> String {
+    // An empty range starts at the line right before it.
+    let start = if len == 0 { start } else { start + 1 };
+    format!("{start},{len}")
+}
+
+fn push_line(diff: &mut String, prefix: char, line: &str) {
+    diff.push(prefix);
+    diff.push_str(line);
+    if !line.ends_with('\n') {
+        diff.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+/// Returns the unified diff between two versions of the file at `path`, or an empty string if
+/// they're the same.
+pub fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    let old_lines = old.split_inclusive('\n').collect::<Vec<_>>();
+    let new_lines = new.split_inclusive('\n').collect::<Vec<_>>();
+    let ops = diff_lines(&old_lines, &new_lines);
+
+    // Group the changes into hunks, merging those whose contexts overlap.
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for (index, _) in ops.iter().enumerate().filter(|(_, op)| op.0 != Op::Equal) {
+        let start = index.saturating_sub(CONTEXT);
+        let end = (index + CONTEXT + 1).min(ops.len());
+        match hunks.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    if hunks.is_empty() {
+        return String::new();
+    }
+
+    let mut diff = format!("--- {path}\n+++ {path}\n");
+    for (start, end) in hunks {
+        let ops = &ops[start..end];
+        let old_len = ops.iter().filter(|op| op.0 != Op::Insert).count();
+        let new_len = ops.iter().filter(|op| op.0 != Op::Delete).count();
+        let _ = writeln!(
+            diff,
+            "@@ -{} +{} @@",
+            range(ops[0].1, old_len),
+            range(ops[0].2, new_len)
+        );
+
+        for &(op, i, j) in ops {
+            match op {
+                Op::Equal => push_line(&mut diff, ' ', old_lines[i]),
+                Op::Delete => push_line(&mut diff, '-', old_lines[i]),
+                Op::Insert => push_line(&mut diff, '+', new_lines[j]),
+            }
+        }
+    }
+    diff
+}
+
+#[test]
+fn test_unified_diff() {
+    let old = "[package]\nname = \"a\"\n\n[dependencies]\nlog = \"0.4\"\nrand = \"0.8\"\nserde = \"1\"\n\n[dev-dependencies]\n\n[build-dependencies]\ncc = \"1\"\n";
+    let new = "[package]\nname = \"a\"\n\n[dependencies]\nlog = \"0.4\"\nserde = \"1\"\n\n[dev-dependencies]\n\n[build-dependencies]\n";
+
+    assert_eq!(unified_diff("Cargo.toml", old, old), "");
+    assert_eq!(
+        unified_diff("Cargo.toml", old, new),
+        "--- Cargo.toml\n+++ Cargo.toml\n\
+         @@ -3,10 +3,8 @@\n \n [dependencies]\n log = \"0.4\"\n-rand = \"0.8\"\n serde = \"1\"\n \n \
+         [dev-dependencies]\n \n [build-dependencies]\n-cc = \"1\"\n"
+    );
+
+    // Distant changes are in separate hunks.
+    let old = (1..=20).map(|i| format!("{i}\n")).collect::<String>();
+    let new = (1..=20)
+        .filter(|i| *i != 2 && *i != 19)
+        .map(|i| format!("{i}\n"))
+        .collect::<String>();
+    assert_eq!(
+        unified_diff("Cargo.toml", &old, &new),
+        "--- Cargo.toml\n+++ Cargo.toml\n\
+         @@ -1,5 +1,4 @@\n 1\n-2\n 3\n 4\n 5\n\
+         @@ -16,5 +15,4 @@\n 16\n 17\n 18\n-19\n 20\n"
+    );
+
+    // Insertions, e.g. of an ignored list.
+    assert_eq!(
+        unified_diff("Cargo.toml", "a\nb\nc\n", "a\nb\nx\ny\nc\n"),
+        "--- Cargo.toml\n+++ Cargo.toml\n@@ -1,3 +1,5 @@\n a\n b\n+x\n+y\n c\n"
+    );
+
+    assert_eq!(
+        unified_diff("Cargo.toml", "a\nb", "a\n"),
+        "--- Cargo.toml\n+++ Cargo.toml\n@@ -1,2 +1,1 @@\n a\n-b\n\\ No newline at end of file\n"
+    );
+}
@@ -0,0 +1,1024 @@
+//! Rewriting of the Cargo.toml files, to remove unused dependencies.
+
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{Context, bail};
+use toml_edit::{Decor, Item, KeyMut, RawString, TableLike};
+
+use crate::{DependencyKind, UnusedDependency};
+
+#[cfg(test)]
+use crate::TOP_LEVEL;
+
+/// Returns dependency tables from top level and target sources.
+fn get_dependency_tables(
+    kv_iter: toml_edit::IterMut<'_>,
+    top_level: bool,
+) -> anyhow::Result<Vec<(KeyMut<'_>, &mut dyn TableLike)>> {
+    let mut matched_tables = Vec::new();
+    for (k, v) in kv_iter {
+        match k.get() {
+            "dependencies" | "build-dependencies" | "dev-dependencies" => {
+                let table = v.as_table_like_mut().context(k.to_string())?;
+                matched_tables.push((k, table));
+            }
+            // handle dependency tables inside target triples or cfg expressions,
+            // ex: `target.'cfg(unix)'.dependencies`, `target.x86_64-pc-windows-msvc.dependencies`
+            // https://doc.rust-lang.org/cargo/reference/specifying-dependencies.html#platform-specific-dependencies
+            "target" if top_level => {
+                let target_table = v.as_table_like_mut().context("target")?;
+                for (_, triple_table) in target_table.iter_mut() {
+                    if let Some(t) = triple_table.as_table_like_mut() {
+                        let mut triple_deps = get_dependency_tables(t.iter_mut(), false)?;
+                        matched_tables.append(&mut triple_deps);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(matched_tables)
+}
+
+/// Returns the part of an entry's prefix which is separated from it by a blank line, e.g. blank
+/// lines or the comment of a group of entries; the rest are the comments right above the entry.
+fn detached_prefix(prefix: &str) -> &str {
+    let mut end = 0;
+    let mut offset = 0;
+    for line in prefix.split_inclusive('\n') {
+        offset += line.len();
+        if line.ends_with('\n') && line.trim().is_empty() {
+            end = offset;
+        }
+    }
+    &prefix[..end]
+}
+
+/// Removes an entry from a table, along with its comments: those right above it, and the one at
+/// the end of its line.
+///
+/// Blank lines and comments separated from the entry by a blank line aren't about it, so they're
+/// moved to the next entry, if there's one.
+fn remove_entry(table: &mut dyn TableLike, name: &str) -> Option<Item> {
+    let detached = table
+        .key(name)
+        .and_then(|key| key.leaf_decor().prefix())
+        .and_then(RawString::as_str)
+        .map(|prefix| detached_prefix(prefix).to_owned())
+        .unwrap_or_default();
+    let next = table
+        .iter()
+        .map(|(key, _)| key)
+        .skip_while(|key| *key != name)
+        .nth(1)
+        .map(str::to_owned);
+
+    let removed = table.remove(name)?;
+
+    if !detached.is_empty()
+        && let Some(next) = next
+        && table.get(&next).is_some_and(Item::is_value)
+        && let Some(mut key) = table.key_mut(&next)
+    {
+        let prefix = key
+            .leaf_decor()
+            .prefix()
+            .and_then(RawString::as_str)
+            .unwrap_or_default();
+        let prefix = format!("{detached}{prefix}");
+        key.leaf_decor_mut().set_prefix(prefix);
+    }
+
+    Some(removed)
+}
+
+fn has_comments(decor: &Decor) -> bool {
+    [decor.prefix(), decor.suffix()]
+        .into_iter()
+        .flatten()
+        .any(|raw| raw.as_str().is_some_and(|raw| raw.contains('#')))
+}
+
+/// Removes the table at the given path if it's been left empty, unless it carries its own
+/// comments. Its parents are then removed too if they were only implicitly declared by it, like
+/// `target.'cfg(unix)'` for `[target.'cfg(unix)'.dependencies]`.
+fn remove_emptied_table(manifest: &mut toml_edit::DocumentMut, path: &[&str], implicit_only: bool) {
+    let Some((name, parents)) = path.split_last() else {
+        return;
+    };
+
+    let mut parent: &mut dyn TableLike = manifest.as_table_mut();
+    for key in parents {
+        match parent.get_mut(key).and_then(Item::as_table_like_mut) {
+            Some(table) => parent = table,
+            None => return,
+        }
+    }
+
+    let removable = match parent.get(name) {
+        Some(Item::Table(table)) => {
+            table.is_empty()
+                && (table.is_implicit() || (!implicit_only && !has_comments(table.decor())))
+        }
+        _ => false,
+    };
+    if removable {
+        parent.remove(name);
+        log::debug!("removed empty table {}", path.join("."));
+        remove_emptied_table(manifest, parents, true);
+    }
+}
+
+/// Returns the text of a raw string, if it's been parsed.
+fn raw_str(raw: Option<&RawString>) -> Option<String> {
+    raw.and_then(RawString::as_str).map(str::to_owned)
+}
+
+/// Returns the text starting at the first line break, if there's one, i.e. without the comment at
+/// the end of the current line.
+fn from_first_line_break(text: &str) -> &str {
+    text.find('\n').map_or(text, |index| &text[index..])
+}
+
+/// Removes the strings matching the predicate from an array, along with their comments, keeping
+/// the layout of the other values.
+fn remove_values(values: &mut toml_edit::Array, mut remove: impl FnMut(&str) -> bool) {
+    let mut index = 0;
+    while index < values.len() {
+        if !values
+            .get(index)
+            .and_then(toml_edit::Value::as_str)
+            .is_some_and(&mut remove)
+        {
+            index += 1;
+            continue;
+        }
+
+        let removed = values.remove(index);
+        let removed_prefix = raw_str(removed.decor().prefix());
+        let removed_suffix = raw_str(removed.decor().suffix());
+
+        if let Some(next) = values.get_mut(index) {
+            // The comment at the end of the removed value's line is in the next value's prefix.
+            if let Some(prefix) = raw_str(next.decor().prefix()) {
+                let prefix = from_first_line_break(&prefix);
+                // On a single line, the spacing before the first value is kept.
+                let prefix = match removed_prefix {
+                    Some(removed_prefix) if index == 0 && !prefix.contains('\n') => removed_prefix,
+                    _ => prefix.to_owned(),
+                };
+                next.decor_mut().set_prefix(prefix);
+            }
+        } else if values.trailing_comma() {
+            // The comment at the end of the removed value's line is in the array's trailing text.
+            if let Some(trailing) = raw_str(Some(values.trailing())) {
+                let trailing = from_first_line_break(&trailing).to_owned();
+                values.set_trailing(trailing);
+            }
+        } else if let Some(suffix) = removed_suffix
+            && let Some(line_break) = suffix.rfind('\n')
+            && let Some(last) = index.checked_sub(1).and_then(|last| values.get_mut(last))
+        {
+            // Keep the closing bracket on its own line.
+            last.decor_mut().set_suffix(&suffix[line_break..]);
+        }
+    }
+}
+
+/// Tells whether a feature's value references one of the removed dependencies or features.
+///
+/// A bare name refers to a dependency's implicit feature, unless an explicit feature has the same
+/// name.
+fn references_removed(
+    value: &str,
+    removed_deps: &[String],
+    features: &[String],
+    removed_features: &[String],
+) -> bool {
+    let is_removed_dep = |name: &str| removed_deps.iter().any(|dep| dep == name);
+
+    if let Some(dep) = value.strip_prefix("dep:") {
+        is_removed_dep(dep)
+    } else if let Some((dep, _feature)) = value.split_once('/') {
+        // Either `dep/feature` or `dep?/feature`.
+        is_removed_dep(dep.strip_suffix('?').unwrap_or(dep))
+    } else {
+        (is_removed_dep(value) && !features.iter().any(|feature| feature == value))
+            || removed_features.iter().any(|feature| feature == value)
+    }
+}
+
+/// Removes the references to the given dependencies from the `[features]` table, so that cargo
+/// still accepts the manifest.
+///
+/// Features which end up empty because of this are removed too, as well as the references to
+/// them.
+fn remove_feature_references(manifest: &mut toml_edit::DocumentMut, removed_deps: &[String]) {
+    let Some(features) = manifest
+        .get_mut("features")
+        .and_then(|features| features.as_table_like_mut())
+    else {
+        return;
+    };
+
+    let names = features
+        .iter()
+        .map(|(name, _)| name.to_owned())
+        .collect::<Vec<_>>();
+    let mut removed_features = Vec::new();
+
+    loop {
+        let mut emptied = Vec::new();
+        for (name, value) in features.iter_mut() {
+            let Some(values) = value.as_array_mut() else {
+                continue;
+            };
+            if values.is_empty() {
+                // Already empty, so not because of a removed dependency.
+                continue;
+            }
+            remove_values(values, |value| {
+                references_removed(value, removed_deps, &names, &removed_features)
+            });
+            if values.is_empty() {
+                emptied.push(name.get().to_owned());
+            }
+        }
+
+        if emptied.is_empty() {
+            break;
+        }
+        for name in emptied {
+            features.remove(&name);
+            log::debug!("removed features.{name}");
+            removed_features.push(name);
+        }
+    }
+}
+
+/// Returns the dependency table in which an unused dependency is declared.
+fn dependency_table<'a>(
+    manifest: &'a mut toml_edit::DocumentMut,
+    dep: &UnusedDependency,
+) -> Option<&'a mut dyn TableLike> {
+    let parent: &mut dyn TableLike = match &dep.target {
+        None => manifest.as_table_mut(),
+        Some(target) => manifest
+            .get_mut("target")?
+            .get_mut(target)?
+            .as_table_like_mut()?,
+    };
+    parent.get_mut(dep.kind.table_name())?.as_table_like_mut()
+}
+
+/// Removes the given unused dependencies from the tables they're declared in, including
+/// platform-specific ones; the same dependency in other tables is left as is. The comments about
+/// them are removed too, as well as the tables they leave empty, unless these have comments.
+///
+/// References in the `[features]` table to dependencies which aren't declared anymore as normal or
+/// build dependencies are removed too.
+pub fn remove_dependencies(
+    manifest: &str,
+    dependencies: &[UnusedDependency],
+) -> anyhow::Result<String> {
+    let mut manifest = toml_edit::DocumentMut::from_str(manifest)?;
+
+    for dep in dependencies {
+        let table_name = dep.table_name();
+        let removed = dependency_table(&mut manifest, dep)
+            .and_then(|table| remove_entry(table, &dep.name))
+            .is_some();
+        if !removed {
+            bail!("{} not found in {table_name}", dep.name);
+        }
+        log::debug!("removed {table_name}.{}", dep.name);
+    }
+
+    for dep in dependencies {
+        let path = match &dep.target {
+            Some(target) => vec!["target", target, dep.kind.table_name()],
+            None => vec![dep.kind.table_name()],
+        };
+        remove_emptied_table(&mut manifest, &path, false);
+    }
+
+    // Features can't enable dev-dependencies, so they don't count.
+    let declared = get_dependency_tables(manifest.iter_mut(), true)?
+        .into_iter()
+        .filter(|(kind, _)| kind.get() != DependencyKind::Development.table_name())
+        .flat_map(|(_, table)| {
+            table
+                .iter()
+                .map(|(name, _)| name.to_owned())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut removed = dependencies
+        .iter()
+        .map(|dep| dep.name.clone())
+        .filter(|name| !declared.contains(name))
+        .collect::<Vec<_>>();
+    removed.sort();
+    removed.dedup();
+
+    remove_feature_references(&mut manifest, &removed);
+
+    let serialized = manifest.to_string();
+    Ok(serialized)
+}
+
+/// Removes the given dependencies from the `[workspace.dependencies]` table of a workspace
+/// manifest, along with the comments about them, and the table if it's left empty.
+pub fn remove_workspace_dependencies(
+    manifest: &str,
+    dependency_list: &[String],
+) -> anyhow::Result<String> {
+    let mut manifest = toml_edit::DocumentMut::from_str(manifest)?;
+
+    let table = manifest
+        .get_mut("workspace")
+        .and_then(|workspace| workspace.get_mut("dependencies"))
+        .and_then(|deps| deps.as_table_like_mut())
+        .context("workspace.dependencies")?;
+
+    for dep in dependency_list {
+        if remove_entry(table, dep).is_some() {
+            log::debug!("removed workspace.dependencies.{dep}");
+        } else {
+            bail!("{dep} not found in workspace.dependencies");
+        }
+    }
+    remove_emptied_table(&mut manifest, &["workspace", "dependencies"], false);
+
+    let serialized = manifest.to_string();
+    Ok(serialized)
+}
+
+/// Returns the prefix of a value inserted in an array: a line break and the indentation of the
+/// other values in a multi-line array, or a space.
+fn array_value_prefix(array: &toml_edit::Array) -> String {
+    array
+        .iter()
+        .filter_map(|value| value.decor().prefix().and_then(RawString::as_str))
+        .find_map(|prefix| {
+            prefix
+                .rfind('\n')
+                .map(|index| format!("\n{}", &prefix[index + 1..]))
+        })
+        .unwrap_or_else(|| " ".to_owned())
+}
+
+/// Adds the given dependencies to the `cargo-machete` ignored list in the `metadata` table of the
+/// `[package]` or `[workspace]` table, creating them if needed. Each name is inserted before the
+/// first greater one, so that a sorted list stays sorted; an unsorted list isn't reordered.
+fn add_to_ignored(manifest: &str, parent: &str, names: &[String]) -> anyhow::Result<String> {
+    if names.is_empty() {
+        return Ok(manifest.to_owned());
+    }
+
+    let mut manifest = toml_edit::DocumentMut::from_str(manifest)?;
+
+    let parent = manifest
+        .get_mut(parent)
+        .and_then(Item::as_table_like_mut)
+        .context(parent.to_owned())?;
+    let metadata = parent
+        .entry("metadata")
+        .or_insert_with(|| {
+            let mut metadata = toml_edit::Table::new();
+            metadata.set_implicit(true);
+            Item::Table(metadata)
+        })
+        .as_table_like_mut()
+        .context("metadata")?;
+    let cargo_machete = metadata
+        .entry("cargo-machete")
+        .or_insert_with(toml_edit::table)
+        .as_table_like_mut()
+        .context("metadata.cargo-machete")?;
+    let ignored = cargo_machete
+        .entry("ignored")
+        .or_insert_with(|| toml_edit::value(toml_edit::Array::new()))
+        .as_array_mut()
+        .context("metadata.cargo-machete.ignored isn't an array")?;
+
+    for name in names {
+        if ignored.iter().any(|value| value.as_str() == Some(name)) {
+            continue;
+        }
+        // Insert before the first greater name, which keeps a sorted list sorted.
+        let index = ignored
+            .iter()
+            .position(|value| value.as_str().is_some_and(|value| value > name.as_str()))
+            .unwrap_or(ignored.len());
+
+        let prefix = array_value_prefix(ignored);
+        let mut value = toml_edit::Value::from(name.as_str());
+        match ignored.get_mut(index) {
+            // The new first value takes the place of the current one, which is moved to the next
+            // line of a multi-line array, or after a space.
+            Some(first) if index == 0 => {
+                let first_prefix = first
+                    .decor()
+                    .prefix()
+                    .and_then(RawString::as_str)
+                    .unwrap_or_default()
+                    .to_owned();
+                first.decor_mut().set_prefix(prefix);
+                value.decor_mut().set_prefix(first_prefix);
+            }
+            None if index == 0 => value.decor_mut().set_prefix(""),
+            _ => value.decor_mut().set_prefix(prefix),
+        }
+        ignored.insert_formatted(index, value);
+        log::debug!("ignored {name}");
+    }
+
+    let serialized = manifest.to_string();
+    Ok(serialized)
+}
+
+/// Adds the given dependencies to the ignored list of the `[package.metadata.cargo-machete]`
+/// table of a package manifest, creating it if needed.
+pub fn add_ignored(manifest: &str, names: &[String]) -> anyhow::Result<String> {
+    add_to_ignored(manifest, "package", names)
+}
+
+/// Adds the given dependencies to the ignored list of the `[workspace.metadata.cargo-machete]`
+/// table of a workspace manifest, creating it if needed.
+pub fn add_workspace_ignored(manifest: &str, names: &[String]) -> anyhow::Result<String> {
+    add_to_ignored(manifest, "workspace", names)
+}
+
+/// Removes the given dependencies from the `cargo-machete` ignored list in the `metadata` table of
+/// the `[package]` or `[workspace]` table. The list is removed if it's left empty, as well as the
+/// `cargo-machete` table if it's left empty too.
+fn remove_from_ignored(manifest: &str, parent: &str, names: &[String]) -> anyhow::Result<String> {
+    let mut manifest = toml_edit::DocumentMut::from_str(manifest)?;
+
+    let cargo_machete = manifest
+        .get_mut(parent)
+        .and_then(|parent| parent.get_mut("metadata"))
+        .and_then(|metadata| metadata.get_mut("cargo-machete"))
+        .and_then(Item::as_table_like_mut)
+        .with_context(|| format!("{parent}.metadata.cargo-machete"))?;
+    let ignored = cargo_machete
+        .get_mut("ignored")
+        .and_then(Item::as_array_mut)
+        .with_context(|| format!("{parent}.metadata.cargo-machete.ignored"))?;
+
+    remove_values(ignored, |value| {
+        let removed = names.iter().any(|name| name == value);
+        if removed {
+            log::debug!("removed {value} from {parent}.metadata.cargo-machete.ignored");
+        }
+        removed
+    });
+
+    if ignored.is_empty() {
+        cargo_machete.remove("ignored");
+        remove_emptied_table(&mut manifest, &[parent, "metadata", "cargo-machete"], false);
+    }
+
+    let serialized = manifest.to_string();
+    Ok(serialized)
+}
+
+/// Removes the given dependencies from the ignored list of the `[package.metadata.cargo-machete]`
+/// table of a package manifest.
+pub fn remove_ignored(manifest: &str, names: &[String]) -> anyhow::Result<String> {
+    remove_from_ignored(manifest, "package", names)
+}
+
+/// Removes the given dependencies from the ignored list of the
+/// `[workspace.metadata.cargo-machete]` table of a workspace manifest.
+pub fn remove_workspace_ignored(manifest: &str, names: &[String]) -> anyhow::Result<String> {
+    remove_from_ignored(manifest, "workspace", names)
+}
+
+/// A manifest rewritten by one or several edits.
+struct ManifestEdit {
+    path: PathBuf,
+    /// Canonical path, to tell whether two paths lead to the same manifest.
+    canonical_path: PathBuf,
+    original: String,
+    fixed: String,
+}
+
+/// The manifests rewritten in fix mode.
+///
+/// Edits are only computed at first, so that nothing is written if one of them fails; then all the
+/// manifests are written at once.
+#[derive(Default)]
+pub struct ManifestEdits {
+    edits: Vec<ManifestEdit>,
+}
+
+/// Returns the path to `path` with an additional suffix, e.g. `Cargo.toml.orig`.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Writes a file through a temporary file in the same directory, renamed over it, so that the file
+/// is never left half-written.
+fn write_atomically(path: &Path, contents: &str) -> anyhow::Result<()> {
+    let temp_path = with_suffix(path, ".cargo-machete.tmp");
+
+    let write = || -> anyhow::Result<()> {
+        let permissions = fs::metadata(path)?.permissions();
+        fs::write(&temp_path, contents)?;
+        fs::set_permissions(&temp_path, permissions)?;
+        fs::File::open(&temp_path)?.sync_all()?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    };
+
+    write().inspect_err(|_| {
+        let _ = fs::remove_file(&temp_path);
+    })
+}
+
+impl ManifestEdits {
+    /// Edits a manifest, on top of the previous edits of the same manifest if there are some.
+    pub fn edit(
+        &mut self,
+        path: &Path,
+        edit: impl FnOnce(&str) -> anyhow::Result<String>,
+    ) -> anyhow::Result<()> {
+        let canonical_path = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+
+        match self
+            .edits
+            .iter_mut()
+            .find(|edit| edit.canonical_path == canonical_path)
+        {
+            Some(previous) => previous.fixed = edit(&previous.fixed)?,
+            None => {
+                let original = fs::read_to_string(path)
+                    .with_context(|| format!("reading {}", path.display()))?;
+                let fixed = edit(&original)?;
+                self.edits.push(ManifestEdit {
+                    path: path.to_owned(),
+                    canonical_path,
+                    original,
+                    fixed,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the path, original contents and fixed contents of each edited manifest.
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &str, &str)> {
+        self.edits.iter().map(|edit| {
+            (
+                edit.path.as_path(),
+                edit.original.as_str(),
+                edit.fixed.as_str(),
+            )
+        })
+    }
+
+    /// Writes all the edited manifests, keeping a copy of the original ones next to them (e.g.
+    /// `Cargo.toml.orig`) if `backup` is set.
+    ///
+    /// If a manifest can't be written, the ones already written are restored, and the backups
+    /// removed.
+    pub fn write(&self, backup: bool) -> anyhow::Result<()> {
+        for (index, edit) in self.edits.iter().enumerate() {
+            let backup_path = with_suffix(&edit.path, ".orig");
+            let written = if backup {
+                fs::write(&backup_path, &edit.original)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|()| write_atomically(&edit.path, &edit.fixed))
+            } else {
+                write_atomically(&edit.path, &edit.fixed)
+            };
+
+            let Err(err) = written else {
+                log::debug!("wrote {}", edit.path.display());
+                continue;
+            };
+
+            // Roll back, including the backup of the manifest which couldn't be written.
+            let mut not_restored = Vec::new();
+            for edit in &self.edits[..index] {
+                if let Err(err) = write_atomically(&edit.path, &edit.original) {
+                    log::error!("couldn't restore {}: {err:#}", edit.path.display());
+                    not_restored.push(edit.path.display().to_string());
+                }
+            }
+            if backup {
+                for edit in &self.edits[..=index] {
+                    let _ = fs::remove_file(with_suffix(&edit.path, ".orig"));
+                }
+            }
+
+            let err = err.context(format!("writing {}", edit.path.display()));
+            if not_restored.is_empty() {
+                return Err(err.context("no manifest has been modified"));
+            }
+            return Err(err.context(format!(
+                "couldn't restore the original content of {}",
+                not_restored.join(", ")
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn unused(name: &str, kind: DependencyKind, target: Option<&str>) -> UnusedDependency {
+    UnusedDependency {
+        name: name.to_owned(),
+        kind,
+        target: target.map(str::to_owned),
+        location: None,
+    }
+}
+
+#[test]
+fn test_remove_dependencies() {
+    let manifest = PathBuf::from(TOP_LEVEL).join("./integration-tests/multi-key-dep/Cargo.toml");
+    let manifest = std::fs::read_to_string(manifest).unwrap();
+
+    // `log-once` is only removed from the table it's unused in.
+    let stripped_manifest = remove_dependencies(
+        &manifest,
+        &[
+            unused("cc", DependencyKind::Normal, Some("cfg(unix)")),
+            unused("log-once", DependencyKind::Development, None),
+            unused("rand", DependencyKind::Build, None),
+        ],
+    )
+    .unwrap();
+    assert_eq!(
+        stripped_manifest,
+        r#"[package]
+name = "multi-key-dep"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+log = "0.4.14"
+
+[target.'cfg(unix)'.dependencies]
+log-once = "0.3.1"
+"#
+    );
+
+    let err = remove_dependencies(
+        &manifest,
+        &[unused("log", DependencyKind::Development, None)],
+    )
+    .unwrap_err();
+    assert_eq!(err.to_string(), "log not found in dev-dependencies");
+}
+
+#[test]
+fn test_remove_target_dependencies() {
+    let manifest =
+        PathBuf::from(TOP_LEVEL).join("./integration-tests/target-dependencies/Cargo.toml");
+    let stripped_manifest = remove_dependencies(
+        &std::fs::read_to_string(manifest).unwrap(),
+        &[
+            unused(
+                "lazy_static",
+                DependencyKind::Normal,
+                Some("x86_64-pc-windows-msvc"),
+            ),
+            unused("rand", DependencyKind::Normal, Some("cfg(unix)")),
+            unused(
+                "lazy_static",
+                DependencyKind::Normal,
+                Some("cfg(any(unix,windows))"),
+            ),
+        ],
+    )
+    .unwrap();
+    assert_eq!(
+        stripped_manifest,
+        r#"[package]
+name = "target-dependencies"
+version = "0.1.0"
+edition = "2021"
+
+[target.'cfg(unix)'.dependencies]
+log = "0.4.14"
+
+[target.'cfg(windows)'.dev-dependencies]
+log-once = "0.3.1"
+
+[target.'cfg(unix)'.build-dependencies]
+serde = "1"
+"#
+    );
+}
+
+#[test]
+fn test_remove_dependencies_comments() {
+    let manifest = r#"[package]
+name = "comments"
+version = "0.1.0"
+
+[dependencies]
+# Logging.
+log = "0.4"
+# Random numbers, only used in the examples.
+rand = "0.8" # TODO: move to dev-dependencies
+
+# Serialization.
+
+# The format of the config.
+toml = "0.8"
+serde = "1"
+
+# Dependencies for unix only.
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+
+[target.'cfg(windows)'.dependencies]
+winapi = "0.3"
+
+[build-dependencies]
+cc = "1" # Compiles the C code.
+"#;
+
+    let stripped_manifest = remove_dependencies(
+        manifest,
+        &[
+            unused("rand", DependencyKind::Normal, None),
+            unused("toml", DependencyKind::Normal, None),
+            unused("libc", DependencyKind::Normal, Some("cfg(unix)")),
+            unused("winapi", DependencyKind::Normal, Some("cfg(windows)")),
+            unused("cc", DependencyKind::Build, None),
+        ],
+    )
+    .unwrap();
+    assert_eq!(
+        stripped_manifest,
+        r#"[package]
+name = "comments"
+version = "0.1.0"
+
+[dependencies]
+# Logging.
+log = "0.4"
+
+# Serialization.
+
+serde = "1"
+
+# Dependencies for unix only.
+[target.'cfg(unix)'.dependencies]
+"#
+    );
+}
+
+#[test]
+fn test_manifest_edits() -> anyhow::Result<()> {
+    let dir = std::env::temp_dir().join(format!("cargo-machete-edits-{}", std::process::id()));
+    let (first, second) = (dir.join("first"), dir.join("second"));
+    fs::create_dir_all(&first)?;
+    fs::create_dir_all(&second)?;
+    let (first, second) = (first.join("Cargo.toml"), second.join("Cargo.toml"));
+    fs::write(&first, "first\n")?;
+    fs::write(&second, "second\n")?;
+
+    // Edits of the same manifest are stacked.
+    let mut edits = ManifestEdits::default();
+    edits.edit(&first, |manifest| Ok(format!("{manifest}edited\n")))?;
+    edits.edit(&dir.join("second/../first/Cargo.toml"), |manifest| {
+        Ok(format!("{manifest}twice\n"))
+    })?;
+    edits.edit(&second, |manifest| Ok(format!("{manifest}edited\n")))?;
+    assert_eq!(edits.iter().count(), 2);
+
+    // Nothing is modified if a manifest can't be written.
+    fs::remove_dir_all(second.parent().unwrap())?;
+    let err = edits.write(true).unwrap_err();
+    assert!(format!("{err:#}").starts_with("no manifest has been modified: writing "));
+    assert_eq!(fs::read_to_string(&first)?, "first\n");
+    assert!(!with_suffix(&first, ".orig").exists());
+
+    fs::create_dir_all(second.parent().unwrap())?;
+    fs::write(&second, "second\n")?;
+    edits.write(true)?;
+    assert_eq!(fs::read_to_string(&first)?, "first\nedited\ntwice\n");
+    assert_eq!(fs::read_to_string(with_suffix(&first, ".orig"))?, "first\n");
+    assert_eq!(fs::read_to_string(&second)?, "second\nedited\n");
+    assert!(!with_suffix(&first, ".cargo-machete.tmp").exists());
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_add_ignored() {
+    let manifest = r#"[package]
+name = "ignored"
+version = "0.1.0"
+
+[dependencies]
+log = "0.4"
+rand = "0.8"
+"#;
+
+    // The table is created right after the `[package]` one.
+    let fixed = add_ignored(manifest, &["rand".to_string()]).unwrap();
+    assert_eq!(
+        fixed,
+        r#"[package]
+name = "ignored"
+version = "0.1.0"
+
+[package.metadata.cargo-machete]
+ignored = ["rand"]
+
+[dependencies]
+log = "0.4"
+rand = "0.8"
+"#
+    );
+    assert!(
+        add_ignored(&fixed, &["rand".to_string(), "log".to_string()])
+            .unwrap()
+            .contains("[package.metadata.cargo-machete]\nignored = [\"log\", \"rand\"]\n")
+    );
+
+    // A new first value of a multi-line array moves the previous one to its own line.
+    let manifest = r#"[package]
+name = "ignored"
+
+[package.metadata.cargo-machete]
+ignored = ["zzz",
+    "zzzz",
+]
+"#;
+    assert!(
+        add_ignored(manifest, &["serde".to_string()])
+            .unwrap()
+            .ends_with("ignored = [\"serde\",\n    \"zzz\",\n    \"zzzz\",\n]\n")
+    );
+
+    // An unsorted list isn't reordered: names go before the first greater one.
+    let manifest = r#"[package]
+name = "ignored"
+
+[package.metadata.cargo-machete]
+ignored = ["b", "d", "a"]
+"#;
+    assert!(
+        add_ignored(manifest, &["c".to_string()])
+            .unwrap()
+            .ends_with("ignored = [\"b\", \"c\", \"d\", \"a\"]\n")
+    );
+
+    let manifest = PathBuf::from(TOP_LEVEL)
+        .join("./integration-tests/unused-workspace-dependencies/Cargo.toml");
+    let fixed = add_workspace_ignored(
+        &std::fs::read_to_string(manifest).unwrap(),
+        &["rand".to_string()],
+    )
+    .unwrap();
+    assert!(
+        fixed.ends_with(
+            "[workspace.metadata.cargo-machete]\nignored = [\"lazy_static\", \"rand\"]\n"
+        )
+    );
+}
+
+#[test]
+fn test_remove_ignored() {
+    let manifest =
+        PathBuf::from(TOP_LEVEL).join("./integration-tests/ignored-dep-workspace/Cargo.toml");
+    let manifest = std::fs::read_to_string(manifest).unwrap();
+    assert_eq!(
+        remove_workspace_ignored(&manifest, &["serde".to_string()]).unwrap(),
+        r#"[workspace]
+members = ["./inner"]
+
+[workspace.dependencies]
+log = "0.4.14"
+serde = "1"
+
+[workspace.metadata.cargo-machete]
+ignored = [
+    "lazy_static",
+    "futures", # actually used but in workspace ignored, should NOT cause a warning
+]
+"#
+    );
+
+    let manifest =
+        PathBuf::from(TOP_LEVEL).join("./integration-tests/ignored-dep-workspace/inner/Cargo.toml");
+    let manifest = std::fs::read_to_string(manifest).unwrap();
+    assert!(
+        remove_ignored(&manifest, &["rand_core".to_string()])
+            .unwrap()
+            .ends_with("[package.metadata.cargo-machete]\nignored = [\n    \"log\"\n]\n")
+    );
+
+    // The table is removed once it's empty.
+    let manifest = r#"[package]
+name = "ignored"
+
+[package.metadata.cargo-machete]
+ignored = ["log", "rand"]
+
+[dependencies]
+log = "0.4"
+"#;
+    let fixed = remove_ignored(manifest, &["log".to_string()]).unwrap();
+    assert!(fixed.contains("[package.metadata.cargo-machete]\nignored = [\"rand\"]\n"));
+    assert_eq!(
+        remove_ignored(&fixed, &["rand".to_string()]).unwrap(),
+        "[package]\nname = \"ignored\"\n\n[dependencies]\nlog = \"0.4\"\n"
+    );
+}
+
+#[test]
+fn test_remove_feature_references() {
+    let manifest = r#"[package]
+name = "features"
+version = "0.1.0"
+
+[dependencies]
+log = "0.4"
+rand = { version = "0.9", optional = true }
+serde = { version = "1", optional = true }
+tokio = { version = "1", optional = true }
+
+[features]
+default = ["std"]
+std = ["log/std", "rand?/std", "serde?/std"]
+random = ["dep:rand"]
+all = ["random", "serde"]
+serde = ["dep:serde", "dep:tokio"]
+tokio = ["dep:tokio", "tokio/rt"]
+async = ["tokio"]
+empty = []
+"#;
+
+    let stripped_manifest = remove_dependencies(
+        manifest,
+        &[
+            unused("rand", DependencyKind::Normal, None),
+            unused("tokio", DependencyKind::Normal, None),
+        ],
+    )
+    .unwrap();
+    assert_eq!(
+        stripped_manifest,
+        r#"[package]
+name = "features"
+version = "0.1.0"
+
+[dependencies]
+log = "0.4"
+serde = { version = "1", optional = true }
+
+[features]
+default = ["std"]
+std = ["log/std", "serde?/std"]
+all = ["serde"]
+serde = ["dep:serde"]
+empty = []
+"#
+    );
+}
+
+#[test]
+fn test_remove_workspace_dependencies() {
+    let manifest = PathBuf::from(TOP_LEVEL)
+        .join("./integration-tests/unused-workspace-dependencies/Cargo.toml");
+    let stripped_manifest = remove_workspace_dependencies(
+        &std::fs::read_to_string(manifest).unwrap(),
+        &["rand".to_string(), "serde".to_string()],
+    )
+    .unwrap();
+    assert_eq!(
+        stripped_manifest,
+        r#"[workspace]
+members = ["member"]
+resolver = "2"
+
+[workspace.dependencies]
+lazy_static = "1.4.0"
+log = "0.4.14"
+log-once = "0.3.1"
+
+[workspace.metadata.cargo-machete]
+ignored = ["lazy_static"]
+"#
+    );
+}
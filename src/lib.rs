@@ -0,0 +1,490 @@
+//! Find unused dependencies with this one weird trick!
+//!
+//! This is the library behind the `cargo-machete` binary. The [`Analyzer`] finds the packages and
+//! workspaces contained in some directories, and analyzes them; results can then be displayed
+//! with one of the [`Printer`]s.
+//!
+//! ```no_run
+//! use cargo_machete::{Analyzer, Finding};
+//!
+//! for analysis in Analyzer::new().path("path/to/project").analyze() {
+//!     let analysis = analysis?;
+//!     for package in &analysis.packages {
+//!         for finding in package.findings() {
+//!             match finding {
+//!                 Finding::Unused(dep) => println!("{}: unused {}", package.package_name, dep.name),
+//!                 Finding::IgnoredButUsed(dep) => println!("{}: used {}", package.package_name, dep.name),
+//!             }
+//!         }
+//!     }
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod diff;
+pub mod fix;
+mod location;
+pub mod printers;
+mod search_unused;
+
+pub use crate::location::Location;
+pub use crate::printers::{AnalyzedPaths, Printer};
+pub use crate::search_unused::{
+    DependencyKey, DependencyKind, Finding, PackageAnalysis, UnusedDependency, WorkspaceAnalysis,
+};
+
+use crate::search_unused::{find_unused, find_unused_workspace_dependencies};
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// Should `cargo metadata` be used to figure out the dependencies' names?
+#[derive(Clone, Copy, Debug)]
+pub enum UseCargoMetadata {
+    Yes,
+    No,
+}
+
+#[cfg(test)]
+impl UseCargoMetadata {
+    fn all() -> &'static [Self] {
+        &[Self::Yes, Self::No]
+    }
+}
+
+/// Which backend to use when looking for usages of dependencies in source files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchBackend {
+    /// Match source files against regular expressions: fast, but imprecise.
+    Regex,
+    /// Parse source files and look at the paths they contain: slower, but more precise.
+    Syntax,
+}
+
+#[cfg(test)]
+impl SearchBackend {
+    fn all() -> &'static [Self] {
+        &[Self::Regex, Self::Syntax]
+    }
+}
+
+struct CollectPathOptions {
+    /// Should we avoid scanning `target` directories?
+    skip_target_dir: bool,
+
+    /// Should we ignore files as specified in .gitignore (in the target directory, or any parent),
+    /// and `.ignore`?
+    respect_ignore_files: bool,
+
+    // As an override to the above `respect_ignore_files`, should we use `.gitignore` overall?
+    //
+    // This is used only in testing, to avoid reading this repository's `.gitignore` file for
+    // testing the `collect_path()` function.
+    override_respect_git_ignore: Option<bool>,
+}
+
+fn collect_paths(path: &Path, options: CollectPathOptions) -> Result<Vec<PathBuf>, ignore::Error> {
+    // Find directory entries.
+    let mut builder = ignore::WalkBuilder::new(path);
+
+    builder.standard_filters(options.respect_ignore_files);
+
+    if let Some(val) = options.override_respect_git_ignore {
+        builder.git_ignore(val);
+    }
+
+    if options.skip_target_dir {
+        builder.filter_entry(|entry| !entry.path().ends_with("target"));
+    }
+
+    let walker = builder.build();
+
+    // Keep only errors and `Cargo.toml` files (filter), then map correct paths into owned
+    // `PathBuf`.
+    walker
+        .into_iter()
+        .filter(|entry| {
+            entry
+                .as_ref()
+                .map_or(true, |entry| entry.file_name() == "Cargo.toml")
+        })
+        .map(|res_entry| res_entry.map(|e| e.into_path()))
+        .collect()
+}
+
+/// Analyzes the dependencies of all the packages and workspaces found in some directories.
+///
+/// Built with [`Analyzer::new`], then configured with the builder methods.
+#[derive(Clone, Debug)]
+pub struct Analyzer {
+    paths: Vec<PathBuf>,
+    use_cargo_metadata: UseCargoMetadata,
+    backend: SearchBackend,
+    skip_target_dir: bool,
+    respect_ignore_files: bool,
+    check_workspace_ignores: bool,
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self {
+            paths: Vec::new(),
+            use_cargo_metadata: UseCargoMetadata::No,
+            backend: SearchBackend::Regex,
+            skip_target_dir: false,
+            respect_ignore_files: true,
+            check_workspace_ignores: false,
+        }
+    }
+}
+
+impl Analyzer {
+    /// Creates an analyzer with the default settings: regular expression search, without
+    /// `cargo metadata`, respecting ignore files.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a directory to analyze recursively.
+    ///
+    /// If no paths are added, the current directory is analyzed.
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.paths.push(path.into());
+        self
+    }
+
+    /// Adds several directories to analyze recursively.
+    pub fn paths(mut self, paths: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        self.paths.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets whether `cargo metadata` is used to figure out the dependencies' names. This may
+    /// modify the `Cargo.lock` files of the analyzed packages.
+    pub fn use_cargo_metadata(mut self, use_cargo_metadata: UseCargoMetadata) -> Self {
+        self.use_cargo_metadata = use_cargo_metadata;
+        self
+    }
+
+    /// Sets the backend used to look for usages of dependencies in source files.
+    pub fn backend(mut self, backend: SearchBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Sets whether `target/` directories are skipped.
+    pub fn skip_target_dir(mut self, skip_target_dir: bool) -> Self {
+        self.skip_target_dir = skip_target_dir;
+        self
+    }
+
+    /// Sets whether files ignored by `.gitignore`, `.ignore`, etc. are skipped.
+    pub fn respect_ignore_files(mut self, respect_ignore_files: bool) -> Self {
+        self.respect_ignore_files = respect_ignore_files;
+        self
+    }
+
+    /// Sets whether the entries of the workspaces' `ignored` lists which no longer hide an unused
+    /// dependency are reported in [`WorkspaceAnalysis::ignored_used`]. Off by default.
+    pub fn check_workspace_ignores(mut self, check_workspace_ignores: bool) -> Self {
+        self.check_workspace_ignores = check_workspace_ignores;
+        self
+    }
+
+    /// Analyzes each of the paths, lazily, in order.
+    pub fn analyze(&self) -> impl Iterator<Item = anyhow::Result<PathAnalysis>> + '_ {
+        let paths = if self.paths.is_empty() {
+            vec![PathBuf::from(".")]
+        } else {
+            self.paths.clone()
+        };
+        paths.into_iter().map(|path| self.analyze_path(&path))
+    }
+
+    /// Analyzes all the packages and workspaces found in the given directory.
+    ///
+    /// Fails only if the directory can't be walked over; errors related to a single manifest are
+    /// reported in [`PathAnalysis::errors`] instead.
+    pub fn analyze_path(&self, path: &Path) -> anyhow::Result<PathAnalysis> {
+        self.analyze_path_with(path, &|_| {})
+    }
+
+    /// Same as [`Analyzer::analyze_path`], but also calls `on_package` with the analysis of each
+    /// package as soon as it's done, e.g. to display results progressively.
+    ///
+    /// `on_package` is called from several threads, in no particular order.
+    pub fn analyze_path_with(
+        &self,
+        path: &Path,
+        on_package: &(dyn Fn(&PackageAnalysis) + Sync),
+    ) -> anyhow::Result<PathAnalysis> {
+        let manifest_path_entries = collect_paths(
+            path,
+            CollectPathOptions {
+                skip_target_dir: self.skip_target_dir,
+                respect_ignore_files: self.respect_ignore_files,
+                override_respect_git_ignore: None,
+            },
+        )?;
+
+        // Run analysis in parallel. This will spawn new rayon tasks when dependencies are effectively
+        // used by any Rust crate.
+        let results = manifest_path_entries
+            .par_iter()
+            .map(|manifest_path| {
+                let result = find_unused(manifest_path, self.use_cargo_metadata, self.backend);
+                if let Ok(Some(analysis)) = &result {
+                    on_package(analysis);
+                }
+                (manifest_path, result)
+            })
+            .collect::<Vec<_>>();
+
+        let mut packages = Vec::new();
+        let mut errors = Vec::new();
+        for (manifest_path, result) in results {
+            match result {
+                Ok(Some(analysis)) => packages.push(analysis),
+                Ok(None) => {
+                    log::info!(
+                        "{} is a virtual manifest for a workspace",
+                        manifest_path.to_string_lossy()
+                    );
+                }
+                Err(err) => errors.push((manifest_path.clone(), err)),
+            }
+        }
+
+        // Now that all the packages have been analyzed, look for workspace dependencies that
+        // aren't inherited by any member. Manifests which couldn't be analyzed are only reported
+        // once.
+        let mut workspaces = Vec::new();
+        for manifest_path in &manifest_path_entries {
+            if errors.iter().any(|(path, _)| path == manifest_path) {
+                continue;
+            }
+            match find_unused_workspace_dependencies(
+                manifest_path,
+                &packages,
+                self.check_workspace_ignores,
+            ) {
+                Ok(Some(analysis)) => workspaces.push(analysis),
+                Ok(None) => {}
+                Err(err) => errors.push((manifest_path.clone(), err)),
+            }
+        }
+
+        Ok(PathAnalysis {
+            path: path.to_owned(),
+            packages,
+            workspaces,
+            errors,
+        })
+    }
+}
+
+/// Results of the analysis of one of the directories given to the [`Analyzer`].
+#[non_exhaustive]
+pub struct PathAnalysis {
+    /// The analyzed directory.
+    pub path: PathBuf,
+
+    /// All the packages found in the directory, including those without any findings.
+    pub packages: Vec<PackageAnalysis>,
+
+    /// All the workspaces found in the directory, including those without any unused workspace
+    /// dependencies.
+    pub workspaces: Vec<WorkspaceAnalysis>,
+
+    /// Manifests which couldn't be analyzed, along with the reason why.
+    pub errors: Vec<(PathBuf, anyhow::Error)>,
+}
+
+impl PathAnalysis {
+    /// Was any unused (non-ignored) dependency found?
+    pub fn has_unused_dependencies(&self) -> bool {
+        self.packages
+            .iter()
+            .any(|analysis| !analysis.unused.is_empty())
+            || self
+                .workspaces
+                .iter()
+                .any(|analysis| !analysis.unused.is_empty())
+    }
+}
+
+/// What prevented a manifest from being analyzed, or a directory from being walked over.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// A file couldn't be read.
+    Io,
+    /// A Cargo.toml file couldn't be parsed, or is invalid.
+    Manifest,
+    /// `cargo metadata` failed.
+    CargoMetadata,
+    /// A directory couldn't be walked over, to find the Cargo.toml files in it.
+    PathCollection,
+    /// Any other error.
+    Other,
+}
+
+impl ErrorKind {
+    /// Classifies an error, according to the first known error in its chain of causes.
+    pub fn of(err: &anyhow::Error) -> Self {
+        err.chain()
+            .find_map(|cause| {
+                if cause.is::<ignore::Error>() {
+                    Some(Self::PathCollection)
+                } else if cause.is::<cargo_toml::Error>() {
+                    Some(Self::Manifest)
+                } else if cause.is::<cargo_metadata::Error>() {
+                    Some(Self::CargoMetadata)
+                } else if cause.is::<std::io::Error>() {
+                    Some(Self::Io)
+                } else {
+                    None
+                }
+            })
+            .unwrap_or(Self::Other)
+    }
+
+    /// The name of the kind of error, in snake case.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Io => "io",
+            Self::Manifest => "manifest",
+            Self::CargoMetadata => "cargo_metadata",
+            Self::PathCollection => "path_collection",
+            Self::Other => "other",
+        }
+    }
+}
+
+#[cfg(test)]
+const TOP_LEVEL: &str = concat!(env!("CARGO_MANIFEST_DIR"));
+
+#[test]
+fn test_ignore_target() {
+    let entries = collect_paths(
+        &PathBuf::from(TOP_LEVEL).join("./integration-tests/with-target/"),
+        CollectPathOptions {
+            skip_target_dir: true,
+            respect_ignore_files: false,
+            override_respect_git_ignore: Some(false),
+        },
+    );
+    assert!(entries.unwrap().is_empty());
+
+    let entries = collect_paths(
+        &PathBuf::from(TOP_LEVEL).join("./integration-tests/with-target/"),
+        CollectPathOptions {
+            skip_target_dir: false,
+            respect_ignore_files: true,
+            override_respect_git_ignore: Some(false),
+        },
+    );
+    assert!(entries.unwrap().is_empty());
+
+    let entries = collect_paths(
+        &PathBuf::from(TOP_LEVEL).join("./integration-tests/with-target/"),
+        CollectPathOptions {
+            skip_target_dir: false,
+            respect_ignore_files: false,
+            override_respect_git_ignore: Some(false),
+        },
+    );
+    assert!(!entries.unwrap().is_empty());
+}
+
+#[test]
+fn test_analyzer() -> anyhow::Result<()> {
+    let analyses = Analyzer::new()
+        .path(PathBuf::from(TOP_LEVEL).join("./integration-tests/unused-workspace-dependencies"))
+        .path(PathBuf::from(TOP_LEVEL).join("./integration-tests/just-unused"))
+        .analyze()
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    assert_eq!(analyses.len(), 2);
+
+    let workspace = &analyses[0];
+    assert!(workspace.errors.is_empty());
+    assert!(workspace.has_unused_dependencies());
+    assert_eq!(workspace.packages.len(), 1);
+    assert!(workspace.packages[0].unused.is_empty());
+    assert_eq!(workspace.workspaces.len(), 1);
+    let unused = &workspace.workspaces[0].unused;
+    assert_eq!(unused.len(), 2);
+    assert_eq!(unused[0].name, "rand");
+    assert_eq!(unused[1].name, "serde");
+
+    let just_unused = &analyses[1];
+    assert_eq!(just_unused.packages.len(), 1);
+    assert!(just_unused.workspaces.is_empty());
+    let findings = just_unused.packages[0].findings().collect::<Vec<_>>();
+    assert!(matches!(&findings[..], [Finding::Unused(dep)] if dep.name == "log"));
+
+    // Packages can be handled as soon as they've been analyzed.
+    let streamed = std::sync::Mutex::new(Vec::new());
+    let analysis = Analyzer::new().analyze_path_with(
+        &PathBuf::from(TOP_LEVEL).join("./integration-tests/with-bench"),
+        &|package| streamed.lock().unwrap().push(package.package_name.clone()),
+    )?;
+    let mut streamed = streamed.into_inner().unwrap();
+    streamed.sort();
+    assert_eq!(streamed, &["my_bench", "sortlib"]);
+    assert_eq!(analysis.packages.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_workspace_with_broken_member() -> anyhow::Result<()> {
+    // `rand` is only inherited by the broken member, and `rand_core` only needs to be ignored for
+    // it: the workspace isn't checked, rather than reporting the former as unused and the latter
+    // as ignored but used (which `--prune-ignores` would remove).
+    let analysis = Analyzer::new().analyze_path(
+        &PathBuf::from(TOP_LEVEL).join("./integration-tests/broken-workspace-member"),
+    )?;
+    assert_eq!(analysis.packages.len(), 1);
+    assert_eq!(analysis.packages[0].package_name, "fine");
+    assert!(analysis.workspaces.is_empty());
+    assert!(!analysis.has_unused_dependencies());
+
+    let errors = analysis
+        .errors
+        .iter()
+        .map(|(path, _)| path.strip_prefix(TOP_LEVEL).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(errors.len(), 2, "{errors:?}");
+    assert!(
+        errors
+            .iter()
+            .any(|path| path.ends_with("crates/broken/Cargo.toml"))
+    );
+    assert!(
+        errors
+            .iter()
+            .any(|path| path.ends_with("broken-workspace-member/Cargo.toml"))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_error_kind() {
+    let io = std::io::Error::new(std::io::ErrorKind::NotFound, "not found");
+    assert_eq!(ErrorKind::of(&anyhow::Error::from(io)), ErrorKind::Io);
+
+    let manifest = cargo_toml::Manifest::from_str("[package").unwrap_err();
+    assert_eq!(
+        ErrorKind::of(&anyhow::Error::from(manifest).context("when reading Cargo.toml")),
+        ErrorKind::Manifest
+    );
+
+    let path = Analyzer::new()
+        .analyze_path(Path::new("./this/does/not/exist"))
+        .err()
+        .expect("a path collection error");
+    assert_eq!(ErrorKind::of(&path), ErrorKind::PathCollection);
+
+    assert_eq!(ErrorKind::of(&anyhow::anyhow!("oops")), ErrorKind::Other);
+}
@@ -0,0 +1,198 @@
+//! Locating the dependencies in the Cargo.toml files, to point at them in reports.
+
+use std::path::Path;
+
+use crate::DependencyKind;
+
+/// Where a dependency is declared in a Cargo.toml file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Location {
+    /// Full name of the table declaring the dependency, e.g. `dependencies` or
+    /// `target.'cfg(unix)'.dependencies`.
+    pub table: String,
+    /// 1-based line of the dependency's key.
+    pub line: usize,
+    /// 1-based column of the dependency's key, in characters.
+    pub column: usize,
+}
+
+/// A Cargo.toml file parsed with the positions of its items, to locate dependencies in it.
+pub(crate) struct ManifestLocator {
+    document: toml_edit::Document<String>,
+}
+
+impl ManifestLocator {
+    pub fn parse(content: String) -> anyhow::Result<Self> {
+        Ok(Self {
+            document: toml_edit::Document::parse(content)?,
+        })
+    }
+
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        Self::parse(std::fs::read_to_string(path)?)
+    }
+
+    /// Returns the location of the key of the given dependency, in the table for the given kind
+    /// and target.
+    pub fn dependency(
+        &self,
+        kind: DependencyKind,
+        target: Option<&str>,
+        name: &str,
+    ) -> Option<Location> {
+        let table = kind.full_table_name(target);
+        match target {
+            Some(target) => self.key(&["target", target, kind.table_name()], table, name),
+            None => self.key(&[kind.table_name()], table, name),
+        }
+    }
+
+    /// Returns the location of the key of the given dependency, in the first dependency table
+    /// declaring it (platform-specific tables coming last).
+    pub fn any_dependency(&self, name: &str) -> Option<Location> {
+        let kinds = [
+            DependencyKind::Normal,
+            DependencyKind::Development,
+            DependencyKind::Build,
+        ];
+
+        let targets = self
+            .document
+            .get("target")
+            .and_then(|targets| targets.as_table_like())
+            .map(|targets| targets.iter().map(|(target, _)| target).collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        std::iter::once(None)
+            .chain(targets.into_iter().map(Some))
+            .flat_map(|target| kinds.iter().map(move |kind| (*kind, target)))
+            .find_map(|(kind, target)| self.dependency(kind, target, name))
+    }
+
+    /// Returns the location of the key of the given dependency, in the `[workspace.dependencies]`
+    /// table.
+    pub fn workspace_dependency(&self, name: &str) -> Option<Location> {
+        self.key(
+            &["workspace", "dependencies"],
+            "workspace.dependencies".to_owned(),
+            name,
+        )
+    }
+
+    /// Returns the location of the given name in the `ignored` list of the
+    /// `[workspace.metadata.cargo-machete]` table.
+    pub fn workspace_ignored(&self, name: &str) -> Option<Location> {
+        let table_path = ["workspace", "metadata", "cargo-machete"];
+        let ignored = self.item(&table_path)?.get("ignored")?.as_array()?;
+        let value = ignored.iter().find(|value| value.as_str() == Some(name))?;
+        self.location(table_path.join("."), value.span()?.start)
+    }
+
+    /// Returns the item at the given path.
+    fn item(&self, path: &[&str]) -> Option<&toml_edit::Item> {
+        let mut item = self.document.as_item();
+        for segment in path {
+            item = item.as_table_like()?.get(segment)?;
+        }
+        Some(item)
+    }
+
+    /// Returns the location of the key `name` in the table at the given path, named `table`.
+    fn key(&self, table_path: &[&str], table: String, name: &str) -> Option<Location> {
+        let (key, _) = self
+            .item(table_path)?
+            .as_table_like()?
+            .get_key_value(name)?;
+        self.location(table, key.span()?.start)
+    }
+
+    /// Returns the location of the given byte offset, in the table named `table`.
+    fn location(&self, table: String, offset: usize) -> Option<Location> {
+        let before = &self.document.raw()[..offset];
+        let line_start = before.rfind('\n').map_or(0, |pos| pos + 1);
+        Some(Location {
+            table,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        })
+    }
+}
+
+#[test]
+fn test_locate_dependencies() -> anyhow::Result<()> {
+    let locator = ManifestLocator::parse(
+        r#"[package]
+name = "located"
+
+[dependencies]
+log = "0.4"
+  serde = { version = "1", features = ["derive"] }
+
+[dev-dependencies.rand]
+version = "0.9"
+
+[target.'cfg(unix)'.build-dependencies]
+cc = "1"
+
+[workspace.dependencies]
+tokio = "1"
+
+[workspace.metadata.cargo-machete]
+ignored = ["tokio", "futures"]
+"#
+        .to_owned(),
+    )?;
+
+    let at = |table: &str, line, column| {
+        Some(Location {
+            table: table.to_owned(),
+            line,
+            column,
+        })
+    };
+
+    assert_eq!(
+        locator.dependency(DependencyKind::Normal, None, "log"),
+        at("dependencies", 5, 1)
+    );
+    assert_eq!(
+        locator.dependency(DependencyKind::Normal, None, "serde"),
+        at("dependencies", 6, 3)
+    );
+    assert_eq!(
+        locator.dependency(DependencyKind::Development, None, "rand"),
+        at("dev-dependencies", 8, 19)
+    );
+    assert_eq!(
+        locator.dependency(DependencyKind::Build, Some("cfg(unix)"), "cc"),
+        at("target.'cfg(unix)'.build-dependencies", 12, 1)
+    );
+    assert_eq!(locator.dependency(DependencyKind::Build, None, "cc"), None);
+    assert_eq!(
+        locator.dependency(DependencyKind::Normal, None, "rand"),
+        None
+    );
+
+    assert_eq!(
+        locator.any_dependency("rand"),
+        at("dev-dependencies", 8, 19)
+    );
+    assert_eq!(
+        locator.any_dependency("cc"),
+        at("target.'cfg(unix)'.build-dependencies", 12, 1)
+    );
+    assert_eq!(locator.any_dependency("tokio"), None);
+
+    assert_eq!(
+        locator.workspace_dependency("tokio"),
+        at("workspace.dependencies", 15, 1)
+    );
+    assert_eq!(
+        locator.workspace_ignored("futures"),
+        at("workspace.metadata.cargo-machete", 18, 21)
+    );
+    assert_eq!(locator.workspace_ignored("log"), None);
+
+    Ok(())
+}
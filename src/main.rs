@@ -1,16 +1,19 @@
-mod printers;
-mod search_unused;
-
-use crate::printers::json::JsonPrinter;
-use crate::printers::stdout::StdoutPrinter;
-use crate::printers::{AnalyzedPaths, Printer};
-use crate::search_unused::find_unused;
-use anyhow::{Context, bail};
-use rayon::prelude::*;
-use std::path::Path;
-use std::str::FromStr;
-use std::{fs, path::PathBuf};
-use toml_edit::{KeyMut, TableLike};
+use cargo_machete::printers::checkstyle::CheckstylePrinter;
+use cargo_machete::printers::codeclimate::CodeClimatePrinter;
+use cargo_machete::printers::github::GithubPrinter;
+use cargo_machete::printers::json::JsonPrinter;
+use cargo_machete::printers::junit::JunitPrinter;
+use cargo_machete::printers::markdown::MarkdownPrinter;
+use cargo_machete::printers::ndjson::NdjsonPrinter;
+use cargo_machete::printers::sarif::SarifPrinter;
+use cargo_machete::printers::stdout::StdoutPrinter;
+use cargo_machete::{
+    AnalyzedPaths, Analyzer, PathAnalysis, Printer, SearchBackend, UseCargoMetadata, diff, fix,
+};
+use std::{
+    io::{self, IsTerminal, Write as _},
+    path::PathBuf,
+};
 
 /// Use jemalloc on 64-bit musl since musl's default allocator is very slow when used from multiple
 /// threads (e.g. when using rayon).
@@ -20,17 +23,27 @@ use toml_edit::{KeyMut, TableLike};
 #[global_allocator]
 static ALLOC: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
 
-#[derive(Clone, Copy)]
-pub(crate) enum UseCargoMetadata {
-    Yes,
-    No,
-}
-
-#[cfg(test)]
-impl UseCargoMetadata {
-    fn all() -> &'static [Self] {
-        &[Self::Yes, Self::No]
-    }
+/// How the results are displayed.
+#[derive(argh::FromArgValue, Clone, Copy, PartialEq)]
+enum OutputFormat {
+    /// Human-readable text.
+    Human,
+    /// A JSON object, for tooling integration.
+    Json,
+    /// One JSON object per line, printed as soon as each package has been analyzed.
+    Ndjson,
+    /// A SARIF 2.1.0 log, for code-scanning dashboards.
+    Sarif,
+    /// GitHub Actions workflow commands, to annotate the Cargo.toml files in pull requests.
+    Github,
+    /// A JUnit XML report, for test-result dashboards.
+    Junit,
+    /// A Checkstyle XML report.
+    Checkstyle,
+    /// A Code Climate JSON report, for GitLab's code quality widget.
+    Codeclimate,
+    /// A Markdown report, e.g. for comments on pull requests.
+    Markdown,
 }
 
 #[derive(argh::FromArgs)]
@@ -49,6 +62,11 @@ struct MacheteArgs {
     #[argh(switch)]
     with_metadata: bool,
 
+    /// parse the Rust source files to find usages of dependencies, instead of matching them against
+    /// regular expressions. Slower, but avoids false positives in comments and string literals.
+    #[argh(switch)]
+    precise: bool,
+
     /// don't analyze anything contained in any target/ directories encountered.
     #[argh(switch)]
     skip_target_dir: bool,
@@ -58,6 +76,39 @@ struct MacheteArgs {
     #[argh(switch)]
     fix: bool,
 
+    /// with --fix, --add-ignores or --prune-ignores, print a unified diff of the changes to the
+    /// Cargo.toml files instead of writing them. The diff goes alone to stdout, the report to
+    /// stderr, and the exit status is 1 if there are changes.
+    #[argh(switch)]
+    dry_run: bool,
+
+    /// add the unused dependencies to the `cargo-machete` ignored list of their Cargo.toml file,
+    /// instead of removing them. Each one goes before the first greater name of the list, which
+    /// keeps a sorted list sorted, but doesn't sort an unsorted one.
+    #[argh(switch)]
+    add_ignores: bool,
+
+    /// remove the dependencies which are actually used from the `cargo-machete` ignored lists. The
+    /// workspace's list is only pruned of the dependencies which no member needs to ignore, which
+    /// are only reported with this flag.
+    #[argh(switch)]
+    prune_ignores: bool,
+
+    /// with --add-ignores, add the unused dependencies of workspace members to the ignored list of
+    /// the workspace's Cargo.toml file, rather than to their own.
+    #[argh(switch)]
+    workspace_ignores: bool,
+
+    /// with --fix, ask what to do with each unused dependency: remove it, ignore it in the
+    /// Cargo.toml file, or skip it.
+    #[argh(switch)]
+    interactive: bool,
+
+    /// with --fix, --add-ignores or --prune-ignores, keep a copy of the original Cargo.toml files
+    /// next to them, as Cargo.toml.orig.
+    #[argh(switch)]
+    backup: bool,
+
     /// also search in ignored files (.gitignore, .ignore, etc.) when searching for files.
     #[argh(switch)]
     no_ignore: bool,
@@ -66,95 +117,278 @@ struct MacheteArgs {
     #[argh(switch)]
     version: bool,
 
-    /// suppress progress and informational output (inactive when --json is passed).
+    /// suppress progress and informational output (only for the human format).
     #[argh(switch, short = 'q')]
     quiet: bool,
 
-    /// output results as JSON for tooling integration.
-    #[argh(switch)]
-    json: bool,
+    /// output format of the results: human (default), json, ndjson to stream results, sarif, github
+    /// for GitHub Actions annotations, junit, checkstyle, codeclimate for GitLab's code quality
+    /// widget, or markdown.
+    #[argh(option, default = "OutputFormat::Human")]
+    format: OutputFormat,
 
     /// paths to directories that must be scanned.
     #[argh(positional, greedy)]
     paths: Vec<PathBuf>,
 }
 
-struct CollectPathOptions {
-    /// Should we avoid scanning `target` directories?
-    skip_target_dir: bool,
+/// Return true if this is run as `cargo machete`, false otherwise (`cargo-machete`, `cargo run -- ...`)
+fn running_as_cargo_cmd() -> bool {
+    // If run under Cargo in general, a `CARGO` environment variable is set.
+    //
+    // But this is also set when running with `cargo run`, which we don't want to break! In that
+    // latter case, another set of cargo variables are defined, which aren't defined when just
+    // running as `cargo machete`. Picked `CARGO_PKG_NAME` as one of those variables.
+    //
+    // So we're running under cargo if `CARGO` is defined, but not `CARGO_PKG_NAME`.
+    std::env::var("CARGO").is_ok() && std::env::var("CARGO_PKG_NAME").is_err()
+}
 
-    /// Should we ignore files as specified in .gitignore (in the target directory, or any parent),
-    /// and `.ignore`?
-    respect_ignore_files: bool,
+/// What to do with an unused dependency, in interactive mode.
+#[derive(Clone, Copy, PartialEq)]
+enum Choice {
+    /// Remove it from the Cargo.toml file.
+    Remove,
+    /// Add it to the `cargo-machete` ignored list of the Cargo.toml file.
+    Ignore,
+    /// Leave it as is.
+    Skip,
+}
 
-    // As an override to the above `respect_ignore_files`, should we use `.gitignore` overall?
-    //
-    // This is used only in testing, to avoid reading this repository's `.gitignore` file for
-    // testing the `collect_path()` function.
-    override_respect_git_ignore: Option<bool>,
+/// Asks what to do with an unused dependency, until a valid answer is given. Once the input has
+/// been closed, all the remaining dependencies are skipped.
+fn ask(question: &str) -> anyhow::Result<Choice> {
+    loop {
+        eprint!("{question} [r]emove, [i]gnore, [s]kip? ");
+        io::stderr().flush()?;
+
+        let mut answer = String::new();
+        if io::stdin().read_line(&mut answer)? == 0 {
+            eprintln!();
+            return Ok(Choice::Skip);
+        }
+        match answer.trim().to_ascii_lowercase().as_str() {
+            "r" | "remove" => return Ok(Choice::Remove),
+            "i" | "ignore" => return Ok(Choice::Ignore),
+            "s" | "skip" => return Ok(Choice::Skip),
+            _ => eprintln!("Please answer r, i or s."),
+        }
+    }
 }
 
-fn collect_paths(path: &Path, options: CollectPathOptions) -> Result<Vec<PathBuf>, ignore::Error> {
-    // Find directory entries.
-    let mut builder = ignore::WalkBuilder::new(path);
+/// Computes the edits fixing the manifests of an analyzed path: removing the unused dependencies,
+/// or asking what to do with each of them in interactive mode.
+fn fix_path(
+    edits: &mut fix::ManifestEdits,
+    analysis: &PathAnalysis,
+    interactive: bool,
+) -> anyhow::Result<()> {
+    let results = analysis
+        .packages
+        .iter()
+        .filter(|analysis| !analysis.unused.is_empty());
+
+    let workspace_results = analysis
+        .workspaces
+        .iter()
+        .filter(|analysis| !analysis.unused.is_empty());
+
+    for analysis in results {
+        let mut unused = Vec::new();
+        let mut ignored = Vec::new();
+        for dep in &analysis.unused {
+            let choice = if interactive {
+                ask(&format!(
+                    "{}: `{}` is unused in [{}].",
+                    analysis.package_name,
+                    dep.name,
+                    dep.table_name()
+                ))?
+            } else {
+                Choice::Remove
+            };
+            match choice {
+                Choice::Remove => unused.push(dep.clone()),
+                Choice::Ignore => ignored.push(dep.name.clone()),
+                Choice::Skip => {}
+            }
+        }
 
-    builder.standard_filters(options.respect_ignore_files);
+        if !unused.is_empty() || !ignored.is_empty() {
+            edits.edit(&analysis.manifest_path, |manifest| {
+                fix::add_ignored(&fix::remove_dependencies(manifest, &unused)?, &ignored)
+            })?;
+        }
+    }
 
-    if let Some(val) = options.override_respect_git_ignore {
-        builder.git_ignore(val);
+    for analysis in workspace_results {
+        let mut unused = Vec::new();
+        let mut ignored = Vec::new();
+        for dep in &analysis.unused {
+            let choice = if interactive {
+                ask(&format!(
+                    "{}: `{}` is unused in [workspace.dependencies].",
+                    analysis.manifest_path.display(),
+                    dep.name
+                ))?
+            } else {
+                Choice::Remove
+            };
+            match choice {
+                Choice::Remove => unused.push(dep.name.clone()),
+                Choice::Ignore => ignored.push(dep.name.clone()),
+                Choice::Skip => {}
+            }
+        }
+
+        if !unused.is_empty() || !ignored.is_empty() {
+            edits.edit(&analysis.manifest_path, |manifest| {
+                fix::add_workspace_ignored(
+                    &fix::remove_workspace_dependencies(manifest, &unused)?,
+                    &ignored,
+                )
+            })?;
+        }
     }
 
-    if options.skip_target_dir {
-        builder.filter_entry(|entry| !entry.path().ends_with("target"));
+    Ok(())
+}
+
+/// Computes the edits adding the unused dependencies of an analyzed path to the `cargo-machete`
+/// ignored lists: the one of each package, or the one of its workspace if `workspace_ignores` is
+/// set.
+fn add_ignores(
+    edits: &mut fix::ManifestEdits,
+    analysis: &PathAnalysis,
+    workspace_ignores: bool,
+) -> anyhow::Result<()> {
+    for package in &analysis.packages {
+        let mut names = package
+            .unused
+            .iter()
+            .map(|dep| dep.name.clone())
+            .collect::<Vec<_>>();
+        if names.is_empty() {
+            continue;
+        }
+        names.sort();
+        names.dedup();
+
+        match package.workspace_manifest_path() {
+            Some(workspace) if workspace_ignores => edits.edit(workspace, |manifest| {
+                fix::add_workspace_ignored(manifest, &names)
+            })?,
+            _ => edits.edit(&package.manifest_path, |manifest| {
+                fix::add_ignored(manifest, &names)
+            })?,
+        }
     }
 
-    let walker = builder.build();
+    // Unused workspace dependencies can only be ignored by the workspace.
+    for workspace in &analysis.workspaces {
+        let names = workspace
+            .unused
+            .iter()
+            .map(|dep| dep.name.clone())
+            .collect::<Vec<_>>();
+        if !names.is_empty() {
+            edits.edit(&workspace.manifest_path, |manifest| {
+                fix::add_workspace_ignored(manifest, &names)
+            })?;
+        }
+    }
 
-    // Keep only errors and `Cargo.toml` files (filter), then map correct paths into owned
-    // `PathBuf`.
-    walker
-        .into_iter()
-        .filter(|entry| {
-            entry
-                .as_ref()
-                .map_or(true, |entry| entry.file_name() == "Cargo.toml")
-        })
-        .map(|res_entry| res_entry.map(|e| e.into_path()))
-        .collect()
+    Ok(())
 }
 
-/// Return true if this is run as `cargo machete`, false otherwise (`cargo-machete`, `cargo run -- ...`)
-fn running_as_cargo_cmd() -> bool {
-    // If run under Cargo in general, a `CARGO` environment variable is set.
-    //
-    // But this is also set when running with `cargo run`, which we don't want to break! In that
-    // latter case, another set of cargo variables are defined, which aren't defined when just
-    // running as `cargo machete`. Picked `CARGO_PKG_NAME` as one of those variables.
-    //
-    // So we're running under cargo if `CARGO` is defined, but not `CARGO_PKG_NAME`.
-    std::env::var("CARGO").is_ok() && std::env::var("CARGO_PKG_NAME").is_err()
+/// Computes the edits removing the dependencies which are actually used from the `cargo-machete`
+/// ignored lists of an analyzed path.
+fn prune_ignores(edits: &mut fix::ManifestEdits, analysis: &PathAnalysis) -> anyhow::Result<()> {
+    for package in &analysis.packages {
+        let names = package
+            .ignored_used
+            .iter()
+            .map(|dep| dep.name.clone())
+            .collect::<Vec<_>>();
+        if !names.is_empty() {
+            edits.edit(&package.manifest_path, |manifest| {
+                fix::remove_ignored(manifest, &names)
+            })?;
+        }
+    }
+
+    // Workspaces are only analyzed along with all their members, so an entry needed by a member
+    // which couldn't be analyzed is never removed.
+    for workspace in &analysis.workspaces {
+        let names = workspace
+            .ignored_used
+            .iter()
+            .map(|dep| dep.name.clone())
+            .collect::<Vec<_>>();
+        if !names.is_empty() {
+            edits.edit(&workspace.manifest_path, |manifest| {
+                fix::remove_workspace_ignored(manifest, &names)
+            })?;
+        }
+    }
+
+    Ok(())
 }
 
+/// Exit status of `cargo-machete` when an error happened.
+const ERROR_EXIT_STATUS: i32 = 2;
+
 /// Runs `cargo-machete`.
-/// Returns Ok with a bool whether any unused dependencies were found, or Err on errors.
-fn run_machete() -> anyhow::Result<bool> {
+/// Returns Ok with the exit status (0 if no unused dependencies were found, 1 if some were, or if
+/// `--dry-run` has changes to show), or Err on errors.
+fn run_machete() -> anyhow::Result<i32> {
     pretty_env_logger::init();
 
-    let mut args: MacheteArgs = if running_as_cargo_cmd() {
+    let args: MacheteArgs = if running_as_cargo_cmd() {
         argh::cargo_from_env()
     } else {
         argh::from_env()
     };
 
-    let printer: Box<dyn Printer> = if args.json {
-        Box::new(JsonPrinter)
-    } else {
-        Box::new(StdoutPrinter {
+    let printer: Box<dyn Printer> = match args.format {
+        OutputFormat::Human => Box::new(StdoutPrinter {
             quiet: args.quiet,
             with_metadata: args.with_metadata,
-        })
+            // The diff of `--dry-run` goes alone to stdout, so that it can be piped to `patch`.
+            to_stderr: args.dry_run,
+        }),
+        OutputFormat::Json => Box::new(JsonPrinter::default()),
+        OutputFormat::Ndjson => Box::new(NdjsonPrinter::default()),
+        OutputFormat::Sarif => Box::new(SarifPrinter::default()),
+        OutputFormat::Github => Box::new(GithubPrinter),
+        OutputFormat::Junit => Box::new(JunitPrinter::default()),
+        OutputFormat::Checkstyle => Box::new(CheckstylePrinter::default()),
+        OutputFormat::Codeclimate => Box::new(CodeClimatePrinter::default()),
+        OutputFormat::Markdown => Box::new(MarkdownPrinter),
     };
 
+    if args.fix && args.add_ignores {
+        anyhow::bail!("--fix and --add-ignores can't be used together");
+    }
+    if (args.dry_run || args.backup) && !args.fix && !args.add_ignores && !args.prune_ignores {
+        anyhow::bail!(
+            "--dry-run and --backup can only be used along with --fix, --add-ignores or \
+             --prune-ignores"
+        );
+    }
+    if args.interactive && !args.fix {
+        anyhow::bail!("--interactive can only be used along with --fix");
+    }
+    if args.workspace_ignores && !args.add_ignores {
+        anyhow::bail!("--workspace-ignores can only be used along with --add-ignores");
+    }
+    if (args.dry_run || args.interactive) && args.format != OutputFormat::Human {
+        anyhow::bail!("--dry-run and --interactive can only be used with the human format");
+    }
+    if args.interactive && !io::stdin().is_terminal() {
+        anyhow::bail!("--interactive needs a terminal to ask questions");
+    }
+
     if args.version {
         printer.print_version(env!("CARGO_PKG_VERSION"))?;
         std::process::exit(0);
@@ -166,232 +400,126 @@ fn run_machete() -> anyhow::Result<bool> {
         printer.print_paths(AnalyzedPaths::Paths(&args.paths));
     }
 
-    if args.paths.is_empty() {
-        args.paths.push(PathBuf::from("."));
-    }
+    let analyzer = Analyzer::new()
+        .use_cargo_metadata(if args.with_metadata {
+            UseCargoMetadata::Yes
+        } else {
+            UseCargoMetadata::No
+        })
+        .backend(if args.precise {
+            SearchBackend::Syntax
+        } else {
+            SearchBackend::Regex
+        })
+        .skip_target_dir(args.skip_target_dir)
+        .respect_ignore_files(!args.no_ignore)
+        .check_workspace_ignores(args.prune_ignores);
+
+    let paths = if args.paths.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        args.paths
+    };
 
     let mut has_unused_dependencies = false;
 
     // Be resilient in case we can't collect some paths due to errors: don't abort execution, so we
     // can run analysis for paths which didn't cause errors.
-    let mut path_collection_errors = Vec::new();
-
-    for path in args.paths {
-        let manifest_path_entries = match collect_paths(
-            &path,
-            CollectPathOptions {
-                skip_target_dir: args.skip_target_dir,
-                respect_ignore_files: !args.no_ignore,
-                override_respect_git_ignore: None,
-            },
-        ) {
-            Ok(entries) => entries,
+    let mut path_collection_errors = 0;
+
+    // In fix mode, the manifests are only written once all of them have been edited.
+    let mut edits = fix::ManifestEdits::default();
+    let mut handling_error = None;
+
+    for path in paths {
+        let analysis = match printer.package_printer() {
+            Some(print_package) => analyzer.analyze_path_with(&path, print_package),
+            None => analyzer.analyze_path(&path),
+        };
+
+        let analysis = match analysis {
+            Ok(analysis) => analysis,
             Err(err) => {
-                path_collection_errors.push(err);
+                printer.print_errors(&[(path, err)]);
+                path_collection_errors += 1;
                 continue;
             }
         };
 
-        let with_metadata = if args.with_metadata {
-            UseCargoMetadata::Yes
-        } else {
-            UseCargoMetadata::No
-        };
-
-        // Run analysis in parallel. This will spawn new rayon tasks when dependencies are effectively
-        // used by any Rust crate.
-        let results = manifest_path_entries
-            .par_iter()
-            .filter_map(
-                |manifest_path| match find_unused(manifest_path, with_metadata) {
-                    Ok(Some(analysis)) => {
-                        if analysis.unused.is_empty() {
-                            None
-                        } else {
-                            Some((analysis, manifest_path))
-                        }
-                    }
-
-                    Ok(None) => {
-                        log::info!(
-                            "{} is a virtual manifest for a workspace",
-                            manifest_path.to_string_lossy()
-                        );
-                        None
-                    }
-
-                    Err(err) => {
-                        eprintln!("error when handling {}: {:#}", manifest_path.display(), err);
-                        None
-                    }
-                },
-            )
-            .collect::<Vec<_>>();
+        printer.print_errors(&analysis.errors);
 
-        has_unused_dependencies = has_unused_dependencies || !results.is_empty();
+        has_unused_dependencies = has_unused_dependencies || analysis.has_unused_dependencies();
 
-        // Display all the results.
-        printer.print_results(&path, &results)?;
+        // Display all the results, and compute the edits.
+        let handled = printer
+            .print_results(&analysis.path, &analysis.packages, &analysis.workspaces)
+            .and_then(|()| {
+                if args.fix {
+                    fix_path(&mut edits, &analysis, args.interactive)
+                } else if args.add_ignores {
+                    add_ignores(&mut edits, &analysis, args.workspace_ignores)
+                } else {
+                    Ok(())
+                }
+            })
+            .and_then(|()| {
+                if args.prune_ignores {
+                    prune_ignores(&mut edits, &analysis)
+                } else {
+                    Ok(())
+                }
+            });
 
-        if args.fix {
-            for (analysis, path) in &results {
-                let fixed = remove_dependencies(&fs::read_to_string(path)?, &analysis.unused)?;
-                fs::write(path, fixed).expect("Cargo.toml write error");
-            }
+        // Stop at the first error, without writing any manifest, but still print the tail.
+        if let Err(err) = handled {
+            handling_error = Some(err);
+            break;
         }
     }
 
-    printer.print_tail(has_unused_dependencies);
-
-    // Report errors, if some happened during path collections.
-    if !path_collection_errors.is_empty() {
-        anyhow::bail!(
-            "Errors when walking over directories:\n{}",
-            path_collection_errors
-                .iter()
-                .map(ToString::to_string)
-                .collect::<Vec<_>>()
-                .join("\n")
-        );
-    }
-
-    Ok(has_unused_dependencies)
-}
-
-/// Returns dependency tables from top level and target sources.
-fn get_dependency_tables(
-    kv_iter: toml_edit::IterMut<'_>,
-    top_level: bool,
-) -> anyhow::Result<Vec<(KeyMut<'_>, &mut dyn TableLike)>> {
-    let mut matched_tables = Vec::new();
-    for (k, v) in kv_iter {
-        match k.get() {
-            "dependencies" | "build-dependencies" | "dev-dependencies" => {
-                let table = v.as_table_like_mut().context(k.to_string())?;
-                matched_tables.push((k, table));
-            }
-            // handle dependency tables inside target triples,
-            // ex: `target.'cfg(unix)'.dependencies`
-            // https://doc.rust-lang.org/cargo/reference/config.html#configuration-format
-            "target" if top_level => {
-                let target_table = v.as_table_like_mut().context("target")?;
-                for (_, triple_table) in target_table
-                    .iter_mut()
-                    .filter(|(k, _)| k.starts_with("cfg("))
-                {
-                    if let Some(t) = triple_table.as_table_like_mut() {
-                        let mut triple_deps = get_dependency_tables(t.iter_mut(), false)?;
-                        matched_tables.append(&mut triple_deps);
-                    }
-                }
+    let mut has_diff = false;
+    let written = match handling_error {
+        Some(err) => Err(err),
+        None if args.dry_run => {
+            for (path, manifest, fixed) in edits.iter() {
+                let diff = diff::unified_diff(&path.to_string_lossy(), manifest, fixed);
+                has_diff = has_diff || !diff.is_empty();
+                print!("{diff}");
             }
-            _ => {}
+            Ok(())
         }
-    }
-    Ok(matched_tables)
-}
+        None => edits.write(args.backup),
+    };
 
-fn remove_dependencies(manifest: &str, dependency_list: &[String]) -> anyhow::Result<String> {
-    let mut manifest = toml_edit::DocumentMut::from_str(manifest)?;
+    // Report errors, if some happened during path collections, when handling the results or when
+    // writing the manifests, but only after the tail, which tells the exit status.
+    let error = match written {
+        Err(err) => Some(err),
+        Ok(()) if path_collection_errors > 0 => Some(anyhow::anyhow!(
+            "couldn't walk over {path_collection_errors} of the directories"
+        )),
+        Ok(()) => None,
+    };
+    // Like `diff`, a dry run fails if there are changes to apply.
+    let exit_status = match (&error, has_unused_dependencies || has_diff) {
+        (Some(_), _) => ERROR_EXIT_STATUS,
+        (None, true) => 1,
+        (None, false) => 0,
+    };
 
-    let mut matched_tables = get_dependency_tables(manifest.iter_mut(), true)?;
+    printer.print_tail(has_unused_dependencies, exit_status);
 
-    for dep in dependency_list {
-        let mut removed_one = false;
-        for (name, table) in &mut matched_tables {
-            if table.remove(dep).is_some() {
-                removed_one = true;
-                log::debug!("removed {name}.{dep}");
-            } else {
-                log::trace!("no match for {name}.{dep}");
-            }
-        }
-        if !removed_one {
-            let tables = matched_tables
-                .iter()
-                .map(|(k, _)| k.to_string())
-                .collect::<Vec<String>>()
-                .join(", ");
-            bail!("{dep} not found in tables:\n\t{tables}");
-        }
+    match error {
+        Some(err) => Err(err),
+        None => Ok(exit_status),
     }
-
-    let serialized = manifest.to_string();
-    Ok(serialized)
 }
 
 fn main() {
-    let exit_code = match run_machete() {
-        Ok(false) => 0,
-        Ok(true) => 1,
-        Err(err) => {
-            eprintln!("Error: {err}");
-            2
-        }
-    };
+    let exit_code = run_machete().unwrap_or_else(|err| {
+        eprintln!("Error: {err:#}");
+        ERROR_EXIT_STATUS
+    });
 
     std::process::exit(exit_code);
 }
-
-#[cfg(test)]
-const TOP_LEVEL: &str = concat!(env!("CARGO_MANIFEST_DIR"));
-
-#[test]
-fn test_ignore_target() {
-    let entries = collect_paths(
-        &PathBuf::from(TOP_LEVEL).join("./integration-tests/with-target/"),
-        CollectPathOptions {
-            skip_target_dir: true,
-            respect_ignore_files: false,
-            override_respect_git_ignore: Some(false),
-        },
-    );
-    assert!(entries.unwrap().is_empty());
-
-    let entries = collect_paths(
-        &PathBuf::from(TOP_LEVEL).join("./integration-tests/with-target/"),
-        CollectPathOptions {
-            skip_target_dir: false,
-            respect_ignore_files: true,
-            override_respect_git_ignore: Some(false),
-        },
-    );
-    assert!(entries.unwrap().is_empty());
-
-    let entries = collect_paths(
-        &PathBuf::from(TOP_LEVEL).join("./integration-tests/with-target/"),
-        CollectPathOptions {
-            skip_target_dir: false,
-            respect_ignore_files: false,
-            override_respect_git_ignore: Some(false),
-        },
-    );
-    assert!(!entries.unwrap().is_empty());
-}
-
-#[test]
-fn test_remove_dependencies() {
-    let manifest = PathBuf::from(TOP_LEVEL).join("./integration-tests/multi-key-dep/Cargo.toml");
-    let stripped_manifest = remove_dependencies(
-        &std::fs::read_to_string(manifest).unwrap(),
-        &["cc".to_string(), "log-once".to_string(), "rand".to_string()],
-    )
-    .unwrap();
-    assert_eq!(
-        stripped_manifest,
-        r#"[package]
-name = "multi-key-dep"
-version = "0.1.0"
-edition = "2021"
-
-[dependencies]
-log = "0.4.14"
-
-[target.'cfg(unix)'.dependencies]
-
-[dev-dependencies]
-
-[build-dependencies]
-"#
-    );
-}
@@ -0,0 +1,112 @@
+//! A printer that will report the results as a Checkstyle XML report, for tools and CI services
+//! consuming this format.
+//!
+//! Each finding is an `<error>` in the `<file>` of its manifest, with the usual attributes, and an
+//! additional `fingerprint` attribute identifying it across runs.
+
+use std::{cell::RefCell, fmt::Write as _, path::Path};
+
+use crate::{
+    PackageAnalysis, WorkspaceAnalysis,
+    printers::{AnalyzedPaths, IGNORED_USED_RULE_ID, Printer, escape_xml, issues, relative_path},
+};
+
+#[derive(Default)]
+pub struct CheckstylePrinter {
+    /// The path of each manifest with findings, along with the `<error>` elements for them.
+    files: RefCell<Vec<(String, Vec<String>)>>,
+}
+
+impl CheckstylePrinter {
+    fn report(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<checkstyle version=\"4.3\">\n");
+
+        for (name, errors) in self.files.take() {
+            let _ = writeln!(xml, r#"  <file name="{}">"#, escape_xml(&name));
+            for error in errors {
+                let _ = writeln!(xml, "    {error}");
+            }
+            xml.push_str("  </file>\n");
+        }
+
+        xml.push_str("</checkstyle>");
+        xml
+    }
+}
+
+impl Printer for CheckstylePrinter {
+    fn print_version(&self, version: &str) -> anyhow::Result<()> {
+        println!("{version}");
+        Ok(())
+    }
+
+    fn print_paths<'a>(&self, _paths: AnalyzedPaths<'a>) {
+        // Print nothing.
+    }
+
+    fn print_results(
+        &self,
+        _path: &Path,
+        results: &[PackageAnalysis],
+        workspaces: &[WorkspaceAnalysis],
+    ) -> anyhow::Result<()> {
+        let mut files = self.files.borrow_mut();
+
+        for issue in issues(results, workspaces) {
+            let name = relative_path(issue.manifest_path);
+
+            // Findings of a manifest are consecutive, so they're grouped in the same `<file>`.
+            let errors = match files.last_mut() {
+                Some((last, errors)) if *last == name => errors,
+                _ => {
+                    files.push((name, Vec::new()));
+                    &mut files.last_mut().unwrap().1
+                }
+            };
+
+            let position = match issue.location {
+                Some(location) => {
+                    format!(r#"line="{}" column="{}" "#, location.line, location.column)
+                }
+                None => r#"line="1" "#.to_owned(),
+            };
+            let severity = if issue.rule_id == IGNORED_USED_RULE_ID {
+                "info"
+            } else {
+                "error"
+            };
+
+            errors.push(format!(
+                r#"<error {position}severity="{severity}" message="{}" source="cargo-machete.{}" fingerprint="{}"/>"#,
+                escape_xml(&issue.message),
+                issue.rule_id,
+                issue.fingerprint
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn print_tail(&self, _has_unused_dependencies: bool, _exit_status: i32) {
+        println!("{}", self.report());
+    }
+}
+
+#[test]
+fn test_checkstyle_printer() -> anyhow::Result<()> {
+    let printer = CheckstylePrinter::default();
+    super::print_fixtures(&printer, &["ignored-dep"])?;
+    let report = printer.report();
+
+    assert!(report.starts_with("<?xml"));
+    assert_eq!(report.matches("<file ").count(), 1);
+    assert!(report.contains(
+        r#"<error line="11" column="1" severity="error" message="`rand` is declared in [dependencies] of ignored-dep, but isn&apos;t used." source="cargo-machete.unused-dependency" fingerprint=""#
+    ));
+    assert!(report.contains(
+        r#"<error line="12" column="1" severity="info" message="`rand_core` is marked as ignored in ignored-dep, but is actually used." source="cargo-machete.ignored-dependency-used" fingerprint=""#
+    ));
+
+    Ok(())
+}
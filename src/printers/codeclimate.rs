@@ -0,0 +1,164 @@
+//! A printer that will report the results as a Code Climate JSON report, as consumed by the code
+//! quality widget of GitLab.
+//!
+//! The report is an array of issues, each with the location of the finding in its Cargo.toml file
+//! and a fingerprint identifying it across runs.
+//!
+//! See https://docs.gitlab.com/ci/testing/code_quality/#code-quality-report-format
+
+use std::{cell::RefCell, path::Path};
+
+use serde::Serialize;
+
+use crate::{
+    PackageAnalysis, WorkspaceAnalysis,
+    printers::{AnalyzedPaths, IGNORED_USED_RULE_ID, Printer, issues, relative_path},
+};
+
+#[derive(Default)]
+pub struct CodeClimatePrinter {
+    issues: RefCell<Vec<CodeClimateIssue>>,
+}
+
+#[derive(Serialize)]
+struct CodeClimateIssue {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    check_name: &'static str,
+    description: String,
+    categories: Vec<&'static str>,
+    severity: &'static str,
+    /// Stable across runs, so that new findings can be told apart from fixed ones.
+    fingerprint: String,
+    location: CodeClimateLocation,
+}
+
+#[derive(Serialize)]
+struct CodeClimateLocation {
+    path: String,
+    lines: Lines,
+}
+
+#[derive(Serialize)]
+struct Lines {
+    begin: usize,
+}
+
+impl Printer for CodeClimatePrinter {
+    fn print_version(&self, version: &str) -> anyhow::Result<()> {
+        println!("{version}");
+        Ok(())
+    }
+
+    fn print_paths<'a>(&self, _paths: AnalyzedPaths<'a>) {
+        // Print nothing.
+    }
+
+    fn print_results(
+        &self,
+        _path: &Path,
+        results: &[PackageAnalysis],
+        workspaces: &[WorkspaceAnalysis],
+    ) -> anyhow::Result<()> {
+        self.issues
+            .borrow_mut()
+            .extend(
+                issues(results, workspaces)
+                    .into_iter()
+                    .map(|issue| CodeClimateIssue {
+                        kind: "issue",
+                        check_name: issue.rule_id,
+                        description: issue.message,
+                        categories: vec!["Clarity"],
+                        severity: if issue.rule_id == IGNORED_USED_RULE_ID {
+                            "info"
+                        } else {
+                            "major"
+                        },
+                        fingerprint: issue.fingerprint,
+                        location: CodeClimateLocation {
+                            path: relative_path(issue.manifest_path),
+                            // The line is required: point at the top of the manifest if unknown.
+                            lines: Lines {
+                                begin: issue.location.map_or(1, |location| location.line),
+                            },
+                        },
+                    }),
+            );
+        Ok(())
+    }
+
+    fn print_tail(&self, _has_unused_dependencies: bool, _exit_status: i32) {
+        println!(
+            "{}",
+            serde_json::to_string(&self.issues.take()).expect("Code Climate report serialization")
+        );
+    }
+}
+
+#[test]
+fn test_codeclimate_printer() -> anyhow::Result<()> {
+    let fixtures = ["ignored-dep", "unused-workspace-dependencies"];
+    let printer = CodeClimatePrinter::default();
+    let mut analyses = super::print_fixtures(&printer, &fixtures)?;
+    let first = serde_json::to_value(printer.issues.take())?;
+    let issues = first.as_array().unwrap();
+    assert_eq!(issues.len(), 4);
+
+    assert_eq!(issues[0]["type"], "issue");
+    assert_eq!(issues[0]["check_name"], "unused-dependency");
+    assert_eq!(issues[0]["severity"], "major");
+    assert_eq!(issues[0]["location"]["lines"]["begin"], 11);
+    assert!(
+        issues[0]["location"]["path"]
+            .as_str()
+            .unwrap()
+            .ends_with("integration-tests/ignored-dep/Cargo.toml")
+    );
+    assert_eq!(issues[1]["check_name"], "ignored-dependency-used");
+    assert_eq!(issues[1]["severity"], "info");
+
+    // Fingerprints are unique, and the same from one run to the next.
+    let fingerprints = issues
+        .iter()
+        .map(|issue| issue["fingerprint"].as_str().unwrap().to_owned())
+        .collect::<std::collections::HashSet<_>>();
+    assert_eq!(fingerprints.len(), 4);
+    let printer = CodeClimatePrinter::default();
+    super::print_fixtures(&printer, &fixtures)?;
+    assert_eq!(serde_json::to_value(printer.issues.take())?, first);
+
+    // They don't depend on the directory cargo-machete is run from either, even for workspaces.
+    let relative = crate::Analyzer::new().analyze_path(Path::new(
+        "./integration-tests/unused-workspace-dependencies",
+    ))?;
+    let printer = CodeClimatePrinter::default();
+    printer.print_results(&relative.path, &relative.packages, &relative.workspaces)?;
+    let relative = serde_json::to_value(printer.issues.take())?;
+    assert_eq!(relative[0]["fingerprint"], issues[2]["fingerprint"]);
+    assert_ne!(
+        relative[0]["location"]["path"],
+        issues[2]["location"]["path"]
+    );
+
+    // Workspaces sharing dependency names are told apart by their path.
+    let workspace = &analyses[1].workspaces[0];
+    let other = crate::WorkspaceAnalysis {
+        manifest_path: std::path::PathBuf::from(crate::TOP_LEVEL)
+            .join("integration-tests/ignored-dep-workspace/Cargo.toml"),
+        unused: workspace.unused.clone(),
+        ignored_used: workspace.ignored_used.clone(),
+    };
+    analyses[1].workspaces.push(other);
+    let printer = CodeClimatePrinter::default();
+    printer.print_results(&analyses[1].path, &[], &analyses[1].workspaces)?;
+    let both = serde_json::to_value(printer.issues.take())?;
+    let both = both.as_array().unwrap();
+    assert_eq!(both.len(), 4);
+    for i in 0..2 {
+        assert_eq!(both[i]["description"], both[i + 2]["description"]);
+        assert_ne!(both[i]["fingerprint"], both[i + 2]["fingerprint"]);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,145 @@
+//! A printer that will report the results as GitHub Actions workflow commands, so that findings
+//! are shown as annotations on the Cargo.toml files, in pull requests.
+//!
+//! See https://docs.github.com/en/actions/reference/workflow-commands-for-github-actions
+
+use std::path::Path;
+
+use crate::{
+    Location, PackageAnalysis, WorkspaceAnalysis,
+    printers::{AnalyzedPaths, IGNORED_USED_RULE_ID, Printer, issues, relative_path},
+};
+
+pub struct GithubPrinter;
+
+/// Escapes the message of a workflow command.
+fn escape_data(data: &str) -> String {
+    data.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escapes the value of a property of a workflow command.
+fn escape_property(property: &str) -> String {
+    escape_data(property)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+/// Formats a workflow command annotating the given manifest, at the dependency's location if
+/// it's known.
+fn command(
+    command: &str,
+    title: &str,
+    manifest_path: &Path,
+    at: Option<&Location>,
+    message: &str,
+) -> String {
+    // Paths are relative to the repository's root, which is usually the working directory.
+    let file = relative_path(manifest_path);
+
+    let mut properties = format!("file={}", escape_property(&file));
+    if let Some(at) = at {
+        properties += &format!(",line={},col={}", at.line, at.column);
+    }
+    properties += &format!(",title={}", escape_property(title));
+
+    format!("::{command} {properties}::{}", escape_data(message))
+}
+
+/// Returns the workflow commands for all the findings, in order.
+fn commands(results: &[PackageAnalysis], workspaces: &[WorkspaceAnalysis]) -> Vec<String> {
+    issues(results, workspaces)
+        .into_iter()
+        .map(|issue| {
+            let level = if issue.rule_id == IGNORED_USED_RULE_ID {
+                "warning"
+            } else {
+                "error"
+            };
+            command(
+                level,
+                issue.title,
+                issue.manifest_path,
+                issue.location,
+                &issue.message,
+            )
+        })
+        .collect()
+}
+
+impl Printer for GithubPrinter {
+    fn print_version(&self, version: &str) -> anyhow::Result<()> {
+        println!("{version}");
+        Ok(())
+    }
+
+    fn print_paths<'a>(&self, _paths: AnalyzedPaths<'a>) {
+        // Print nothing.
+    }
+
+    fn print_results(
+        &self,
+        _path: &Path,
+        results: &[PackageAnalysis],
+        workspaces: &[WorkspaceAnalysis],
+    ) -> anyhow::Result<()> {
+        for command in commands(results, workspaces) {
+            println!("{command}");
+        }
+        Ok(())
+    }
+
+    fn print_tail(&self, _has_unused_dependencies: bool, _exit_status: i32) {
+        // Print nothing.
+    }
+}
+
+#[test]
+fn test_github_printer() -> anyhow::Result<()> {
+    let analyses =
+        super::print_fixtures(&GithubPrinter, &["ignored-dep", "ignored-dep-workspace"])?;
+
+    let commands = self::commands(&analyses[0].packages, &[]);
+    assert_eq!(commands.len(), 2);
+
+    let file = escape_property(&relative_path(&analyses[0].packages[0].manifest_path));
+    assert_eq!(
+        commands[0],
+        format!(
+            "::error file={file},line=11,col=1,title=Unused dependency::`rand` is declared in \
+             [dependencies] of ignored-dep, but isn't used."
+        )
+    );
+    assert_eq!(
+        commands[1],
+        format!(
+            "::warning file={file},line=12,col=1,title=Ignored dependency is used::`rand_core` is \
+             marked as ignored in ignored-dep, but is actually used."
+        )
+    );
+
+    // Workspace findings are annotated too, including stale ignored entries.
+    let workspace_commands = self::commands(&[], &analyses[1].workspaces);
+    assert_eq!(workspace_commands.len(), 2);
+
+    let file = escape_property(&relative_path(&analyses[1].workspaces[0].manifest_path));
+    assert_eq!(
+        workspace_commands[0],
+        format!(
+            "::error file={file},line=5,col=1,title=Unused workspace dependency::`log` is declared \
+             in [workspace.dependencies], but isn't inherited by any member."
+        )
+    );
+    assert_eq!(
+        workspace_commands[1],
+        format!(
+            "::warning file={file},line=12,col=5,title=Unneeded ignored workspace dependency::\
+             `futures` is marked as ignored in the workspace, but no member needs it to be."
+        )
+    );
+
+    assert_eq!(escape_property("a:b,c%d\ne"), "a%3Ab%2Cc%25d%0Ae");
+
+    Ok(())
+}
@@ -1,22 +1,227 @@
 //! A printer that will report the results as JSON.
+//!
+//! The shape of the output is described by the JSON Schema in `schema/output.schema.json`, whose
+//! version is given by the `schema_version` field.
 
-use std::path::{Path, PathBuf};
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+};
 
 use serde::Serialize;
 
 use crate::{
+    DependencyKind, ErrorKind, Finding, Location, PackageAnalysis, WorkspaceAnalysis,
     printers::{AnalyzedPaths, Printer},
-    search_unused::PackageAnalysis,
 };
 
-pub struct JsonPrinter;
+/// Version of the schema of the JSON output, to be bumped on any breaking change.
+pub(super) const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Default)]
+pub struct JsonPrinter {
+    crates: RefCell<Vec<CrateResults>>,
+    workspaces: RefCell<Vec<WorkspaceResults>>,
+    errors: RefCell<Vec<JsonError>>,
+}
+
+/// JSON output structure for the whole analysis.
+#[derive(Serialize)]
+struct JsonOutput {
+    /// Version of the schema of this document.
+    schema_version: u32,
+    /// List of all the analyzed crates, including those without unused dependencies.
+    crates: Vec<CrateResults>,
+    /// List of all the analyzed workspaces, including those without unused workspace
+    /// dependencies.
+    workspaces: Vec<WorkspaceResults>,
+    /// List of the manifests which couldn't be analyzed, and the paths which couldn't be walked
+    /// over.
+    errors: Vec<JsonError>,
+}
+
+/// JSON structure for an error which prevented an analysis.
+#[derive(Serialize)]
+pub(super) struct JsonError {
+    /// Path to the manifest which couldn't be analyzed, or to the directory which couldn't be
+    /// walked over.
+    path: String,
+    /// Kind of error, e.g. `manifest` or `cargo_metadata`.
+    kind: &'static str,
+    /// Description of the error, with its causes.
+    message: String,
+}
+
+/// JSON structure for a single crate's results.
+#[derive(Serialize)]
+pub(super) struct CrateResults {
+    /// The name of the package.
+    package_name: String,
+    /// Path to the Cargo.toml file.
+    manifest_path: String,
+    /// List of unused dependency names, once each even if they're declared in several tables.
+    unused: Vec<String>,
+    /// List of unused dev-dependency names, once each.
+    unused_dev: Vec<String>,
+    /// List of unused build-dependency names, once each.
+    unused_build: Vec<String>,
+    /// List of dependencies marked as ignored but actually used.
+    ignored_used: Vec<String>,
+    /// All of the above, one per table, along with where they're declared in the Cargo.toml file.
+    findings: Vec<JsonFinding>,
+}
+
+/// JSON structure for a single workspace's results.
+#[derive(Serialize)]
+pub(super) struct WorkspaceResults {
+    /// Path to the workspace Cargo.toml file.
+    manifest_path: String,
+    /// List of `[workspace.dependencies]` entries no member inherits.
+    unused: Vec<String>,
+    /// List of names marked as ignored in the workspace metadata, which don't need to be.
+    ignored_used: Vec<String>,
+    /// All of the above, along with where they're declared in the Cargo.toml file.
+    findings: Vec<JsonFinding>,
+}
+
+/// JSON structure for a single finding, pointing at the dependency's key.
+#[derive(Serialize)]
+struct JsonFinding {
+    /// Either `unused` or `ignored_used`.
+    finding: &'static str,
+    /// Name of the dependency.
+    name: String,
+    /// Full name of the table declaring the dependency, if known.
+    table: Option<String>,
+    /// 1-based line of the dependency's key (or ignored entry), if known.
+    line: Option<usize>,
+    /// 1-based column of the dependency's key (or ignored entry), if known.
+    column: Option<usize>,
+}
+
+impl JsonFinding {
+    fn new(
+        finding: &'static str,
+        name: &str,
+        table: Option<String>,
+        location: Option<&Location>,
+    ) -> Self {
+        Self {
+            finding,
+            name: name.to_owned(),
+            table: location.map(|loc| loc.table.clone()).or(table),
+            line: location.map(|loc| loc.line),
+            column: location.map(|loc| loc.column),
+        }
+    }
+}
+
+impl JsonError {
+    pub(super) fn new(path: &Path, err: &anyhow::Error) -> Self {
+        Self {
+            path: path.to_string_lossy().to_string(),
+            kind: ErrorKind::of(err).as_str(),
+            message: format!("{err:#}"),
+        }
+    }
+}
+
+/// Lists the given names once each, in order: a dependency declared in several tables (e.g. for
+/// several targets) has a finding for each of them, but is only named once.
+fn unique_names<'a>(names: impl Iterator<Item = &'a String>) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::new();
+    for name in names {
+        if !unique.contains(name) {
+            unique.push(name.clone());
+        }
+    }
+    unique
+}
+
+impl CrateResults {
+    pub(super) fn new(analysis: &PackageAnalysis) -> Self {
+        let unused_of_kind = |kind| {
+            unique_names(
+                analysis
+                    .unused
+                    .iter()
+                    .filter(|dep| dep.kind == kind)
+                    .map(|dep| &dep.name),
+            )
+        };
+
+        Self {
+            package_name: analysis.package_name.clone(),
+            manifest_path: analysis.manifest_path.to_string_lossy().to_string(),
+            unused: unused_of_kind(DependencyKind::Normal),
+            unused_dev: unused_of_kind(DependencyKind::Development),
+            unused_build: unused_of_kind(DependencyKind::Build),
+            ignored_used: unique_names(analysis.ignored_used.iter().map(|dep| &dep.name)),
+            findings: analysis
+                .findings()
+                .map(|finding| match finding {
+                    Finding::Unused(dep) => JsonFinding::new(
+                        "unused",
+                        &dep.name,
+                        Some(dep.table_name()),
+                        dep.location.as_ref(),
+                    ),
+                    Finding::IgnoredButUsed(dep) => {
+                        JsonFinding::new("ignored_used", &dep.name, None, dep.location.as_ref())
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+impl WorkspaceResults {
+    pub(super) fn new(analysis: &WorkspaceAnalysis) -> Self {
+        Self {
+            manifest_path: analysis.manifest_path.to_string_lossy().to_string(),
+            unused: unique_names(analysis.unused.iter().map(|dep| &dep.name)),
+            ignored_used: unique_names(analysis.ignored_used.iter().map(|dep| &dep.name)),
+            findings: analysis
+                .unused
+                .iter()
+                .map(|dep| {
+                    JsonFinding::new(
+                        "unused",
+                        &dep.name,
+                        Some("workspace.dependencies".to_owned()),
+                        dep.location.as_ref(),
+                    )
+                })
+                .chain(analysis.ignored_used.iter().map(|dep| {
+                    JsonFinding::new(
+                        "ignored_used",
+                        &dep.name,
+                        Some("workspace.metadata.cargo-machete".to_owned()),
+                        dep.location.as_ref(),
+                    )
+                }))
+                .collect(),
+        }
+    }
+}
+
+impl JsonPrinter {
+    fn output(&self) -> JsonOutput {
+        JsonOutput {
+            schema_version: SCHEMA_VERSION,
+            crates: self.crates.take(),
+            workspaces: self.workspaces.take(),
+            errors: self.errors.take(),
+        }
+    }
+}
 
 impl Printer for JsonPrinter {
     fn print_version(&self, version: &str) -> anyhow::Result<()> {
-        /// JSON output structure for unused dependencies.
+        /// JSON output structure for the version.
         #[derive(Serialize)]
         struct VersionOutput<'a> {
-            /// List of crates with unused dependencies.
+            /// Version of cargo-machete.
             version: &'a str,
         }
 
@@ -30,57 +235,138 @@ impl Printer for JsonPrinter {
         // Print nothing, Jon Snow.
     }
 
-    fn print_results<'a>(
+    fn print_results(
         &self,
         _path: &Path,
-        results: &'a [(PackageAnalysis, &'a PathBuf)],
+        results: &[PackageAnalysis],
+        workspaces: &[WorkspaceAnalysis],
     ) -> anyhow::Result<()> {
-        /// JSON structure for a single crate's unused dependencies.
-        #[derive(Serialize)]
-        struct CrateUnusedDeps {
-            /// The name of the package.
-            package_name: String,
-            /// Path to the Cargo.toml file.
-            manifest_path: String,
-            /// List of unused dependency names.
-            unused: Vec<String>,
-            /// List of dependencies marked as ignored but actually used.
-            ignored_used: Vec<String>,
-        }
+        self.crates
+            .borrow_mut()
+            .extend(results.iter().map(CrateResults::new));
+        self.workspaces
+            .borrow_mut()
+            .extend(workspaces.iter().map(WorkspaceResults::new));
+        Ok(())
+    }
 
-        /// JSON output structure for unused dependencies.
-        #[derive(Serialize)]
-        struct JsonOutput {
-            /// List of crates with unused dependencies.
-            crates: Vec<CrateUnusedDeps>,
-        }
+    fn print_errors(&self, errors: &[(PathBuf, anyhow::Error)]) {
+        self.errors
+            .borrow_mut()
+            .extend(errors.iter().map(|(path, err)| JsonError::new(path, err)));
+    }
 
-        if results.is_empty() {
-            // Render an empty JSON object.
-            println!("{{}}");
-            return Ok(());
-        }
+    fn print_tail(&self, _has_unused_dependencies: bool, _exit_status: i32) {
+        println!(
+            "{}",
+            serde_json::to_string(&self.output()).expect("JSON output serialization")
+        );
+    }
+}
 
-        let mut json_output = JsonOutput {
-            crates: Vec::with_capacity(results.len()),
-        };
+#[test]
+fn test_json_output_schema() -> anyhow::Result<()> {
+    let schema: serde_json::Value =
+        serde_json::from_str(include_str!("../../schema/output.schema.json"))?;
+    let validator = jsonschema::validator_for(&schema)?;
+    let check = |output: &serde_json::Value| {
+        let errors = validator
+            .iter_errors(output)
+            .map(|err| format!("{}: {err}", err.instance_path()))
+            .collect::<Vec<_>>();
+        assert!(
+            errors.is_empty(),
+            "{output} doesn't match the schema: {errors:#?}"
+        );
+    };
 
-        // Collect results for JSON output.
-        for (analysis, path) in results {
-            json_output.crates.push(CrateUnusedDeps {
-                package_name: analysis.package_name.clone(),
-                manifest_path: path.to_string_lossy().to_string(),
-                unused: analysis.unused.clone(),
-                ignored_used: analysis.ignored_used.clone(),
-            });
-        }
+    let printer = JsonPrinter::default();
+    super::print_fixtures(
+        &printer,
+        &[
+            "ignored-dep",
+            "target-dependencies",
+            "unused-workspace-dependencies",
+            "ignored-dep-workspace",
+        ],
+    )?;
+    printer.print_errors(&[(
+        PathBuf::from("broken/Cargo.toml"),
+        anyhow::Error::from(cargo_toml::Manifest::from_str("[package").unwrap_err()),
+    )]);
+    let output = serde_json::to_value(printer.output())?;
+    check(&output);
 
-        println!("{}", serde_json::to_string(&json_output)?);
+    assert_eq!(output["schema_version"], SCHEMA_VERSION);
 
-        Ok(())
-    }
+    // All the packages are listed, even those without unused dependencies.
+    let names = output["crates"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|krate| krate["package_name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        &[
+            "ignored-dep",
+            "target-dependencies",
+            "member",
+            "ignored-dep"
+        ]
+    );
+    assert_eq!(output["crates"][2]["unused"], serde_json::json!([]));
 
-    fn print_tail(&self, _has_unused_dependencies: bool) {
-        // Print nothing.
-    }
+    // Dependencies unused in several target tables are named once, but have a finding for each.
+    let krate = &output["crates"][1];
+    assert_eq!(krate["unused"], serde_json::json!(["lazy_static", "rand"]));
+    let tables = krate["findings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|finding| finding["name"] == "lazy_static")
+        .map(|finding| finding["table"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(tables.len(), 2);
+    assert_ne!(tables[0], tables[1]);
+    assert_eq!(output["workspaces"].as_array().unwrap().len(), 2);
+
+    // Workspaces report their stale ignored entries when asked to, located in the `ignored` list.
+    let workspace = &output["workspaces"][1];
+    assert_eq!(workspace["ignored_used"], serde_json::json!(["futures"]));
+    assert_eq!(
+        workspace["findings"][1],
+        serde_json::json!({
+            "finding": "ignored_used",
+            "name": "futures",
+            "table": "workspace.metadata.cargo-machete",
+            "line": 12,
+            "column": 5
+        })
+    );
+
+    // Errors are reported along with the results.
+    let errors = output["errors"].as_array().unwrap();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0]["path"], "broken/Cargo.toml");
+    assert_eq!(errors[0]["kind"], "manifest");
+    assert!(!errors[0]["message"].as_str().unwrap().is_empty());
+
+    // Without any result, the document has the same shape.
+    let empty = serde_json::to_value(JsonPrinter::default().output())?;
+    check(&empty);
+    assert_eq!(
+        empty,
+        serde_json::json!({
+            "schema_version": SCHEMA_VERSION,
+            "crates": [],
+            "workspaces": [],
+            "errors": []
+        })
+    );
+
+    // The schema itself catches mistakes.
+    assert!(!validator.is_valid(&serde_json::json!({ "crates": 1 })));
+
+    Ok(())
 }
@@ -0,0 +1,267 @@
+//! A printer that will report the results as a JUnit XML report, for test-result dashboards.
+//!
+//! Each analyzed path is a test suite, in which each package (and workspace) is a test case. A test
+//! case fails if the package has unused dependencies.
+
+use std::{cell::RefCell, fmt::Write as _, path::Path};
+
+use crate::{
+    DependencyKey, PackageAnalysis, WorkspaceAnalysis,
+    printers::{AnalyzedPaths, Printer, at, escape_xml},
+};
+
+#[derive(Default)]
+pub struct JunitPrinter {
+    suites: RefCell<Vec<TestSuite>>,
+}
+
+struct TestSuite {
+    name: String,
+    cases: Vec<TestCase>,
+}
+
+struct TestCase {
+    name: String,
+    classname: String,
+    /// The names of the unused dependencies, and a description of each of them.
+    unused: Vec<(String, String)>,
+    /// Additional information about the analysis, which doesn't make the test case fail.
+    notes: Vec<String>,
+}
+
+/// Describes the dependencies marked as ignored in the given manifest, which don't need to be for
+/// the given reason.
+fn ignored_used_notes(
+    manifest_path: &Path,
+    ignored_used: &[DependencyKey],
+    reason: &str,
+) -> Vec<String> {
+    ignored_used
+        .iter()
+        .map(|dep| {
+            format!(
+                "{}{} was marked as ignored, but {reason}!",
+                dep.name,
+                at(manifest_path, dep.location.as_ref())
+            )
+        })
+        .collect()
+}
+
+impl TestCase {
+    fn package(analysis: &PackageAnalysis) -> Self {
+        let path = &analysis.manifest_path;
+        Self {
+            name: analysis.package_name.clone(),
+            classname: path.to_string_lossy().into_owned(),
+            unused: analysis
+                .unused
+                .iter()
+                .map(|dep| {
+                    (
+                        dep.name.clone(),
+                        format!(
+                            "{} (in [{}]){}",
+                            dep.name,
+                            dep.table_name(),
+                            at(path, dep.location.as_ref())
+                        ),
+                    )
+                })
+                .collect(),
+            notes: ignored_used_notes(path, &analysis.ignored_used, "is actually used"),
+        }
+    }
+
+    fn workspace(analysis: &WorkspaceAnalysis) -> Self {
+        let path = &analysis.manifest_path;
+        Self {
+            name: "workspace".to_owned(),
+            classname: path.to_string_lossy().into_owned(),
+            unused: analysis
+                .unused
+                .iter()
+                .map(|dep| {
+                    (
+                        dep.name.clone(),
+                        format!(
+                            "{} (in [workspace.dependencies]){}",
+                            dep.name,
+                            at(path, dep.location.as_ref())
+                        ),
+                    )
+                })
+                .collect(),
+            notes: ignored_used_notes(path, &analysis.ignored_used, "no member needs it to be"),
+        }
+    }
+
+    fn failed(&self) -> bool {
+        !self.unused.is_empty()
+    }
+}
+
+impl JunitPrinter {
+    fn report(&self) -> String {
+        let suites = self.suites.take();
+
+        let count = |suites: &[TestSuite], failed_only: bool| -> usize {
+            suites
+                .iter()
+                .flat_map(|suite| &suite.cases)
+                .filter(|case| !failed_only || case.failed())
+                .count()
+        };
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            xml,
+            r#"<testsuites name="cargo-machete" tests="{}" failures="{}">"#,
+            count(&suites, false),
+            count(&suites, true)
+        );
+
+        for suite in &suites {
+            let _ = writeln!(
+                xml,
+                r#"  <testsuite name="{}" tests="{}" failures="{}">"#,
+                escape_xml(&suite.name),
+                count(std::slice::from_ref(suite), false),
+                count(std::slice::from_ref(suite), true)
+            );
+
+            for case in &suite.cases {
+                let _ = write!(
+                    xml,
+                    r#"    <testcase name="{}" classname="{}""#,
+                    escape_xml(&case.name),
+                    escape_xml(&case.classname)
+                );
+
+                if !case.failed() && case.notes.is_empty() {
+                    xml.push_str("/>\n");
+                    continue;
+                }
+                xml.push_str(">\n");
+
+                if case.failed() {
+                    let names = case
+                        .unused
+                        .iter()
+                        .map(|(name, _)| name.as_str())
+                        .collect::<Vec<_>>();
+                    let details = case
+                        .unused
+                        .iter()
+                        .map(|(_, details)| details.as_str())
+                        .collect::<Vec<_>>();
+                    let _ = writeln!(
+                        xml,
+                        r#"      <failure message="unused dependencies: {}" type="unused-dependencies">{}</failure>"#,
+                        escape_xml(&names.join(", ")),
+                        escape_xml(&details.join("\n"))
+                    );
+                }
+
+                if !case.notes.is_empty() {
+                    let _ = writeln!(
+                        xml,
+                        "      <system-out>{}</system-out>",
+                        escape_xml(&case.notes.join("\n"))
+                    );
+                }
+
+                xml.push_str("    </testcase>\n");
+            }
+
+            xml.push_str("  </testsuite>\n");
+        }
+
+        xml.push_str("</testsuites>");
+        xml
+    }
+}
+
+impl Printer for JunitPrinter {
+    fn print_version(&self, version: &str) -> anyhow::Result<()> {
+        println!("{version}");
+        Ok(())
+    }
+
+    fn print_paths<'a>(&self, _paths: AnalyzedPaths<'a>) {
+        // Print nothing.
+    }
+
+    fn print_results(
+        &self,
+        path: &Path,
+        results: &[PackageAnalysis],
+        workspaces: &[WorkspaceAnalysis],
+    ) -> anyhow::Result<()> {
+        self.suites.borrow_mut().push(TestSuite {
+            name: path.to_string_lossy().into_owned(),
+            cases: results
+                .iter()
+                .map(TestCase::package)
+                .chain(workspaces.iter().map(TestCase::workspace))
+                .collect(),
+        });
+        Ok(())
+    }
+
+    fn print_tail(&self, _has_unused_dependencies: bool, _exit_status: i32) {
+        println!("{}", self.report());
+    }
+}
+
+#[test]
+fn test_junit_printer() -> anyhow::Result<()> {
+    let printer = JunitPrinter::default();
+    super::print_fixtures(
+        &printer,
+        &[
+            "ignored-dep",
+            "unused-workspace-dependencies",
+            "ignored-dep-workspace",
+        ],
+    )?;
+    let report = printer.report();
+    let base = std::path::PathBuf::from(crate::TOP_LEVEL).join("integration-tests");
+
+    assert!(report.starts_with("<?xml"));
+    assert!(report.contains(r#"<testsuites name="cargo-machete" tests="5" failures="4">"#));
+
+    // The unused dependency fails the package, while the ignored one is merely noted.
+    let ignored_dep = base.join("ignored-dep").to_string_lossy().into_owned();
+    assert!(report.contains(&format!(
+        r#"<testsuite name="{ignored_dep}" tests="1" failures="1">"#
+    )));
+    assert!(report.contains(&format!(
+        r#"<failure message="unused dependencies: rand" type="unused-dependencies">rand (in [dependencies]) at {ignored_dep}/Cargo.toml:11:1</failure>"#
+    )));
+    assert!(report.contains(&format!(
+        "<system-out>rand_core at {ignored_dep}/Cargo.toml:12:1 was marked as ignored, but is \
+         actually used!</system-out>"
+    )));
+
+    // The member uses all its dependencies, but the workspace declares unused ones.
+    assert!(report.contains(r#"<testcase name="member" classname=""#));
+    assert!(report.contains(r#"/member/Cargo.toml"/>"#));
+    assert!(report.contains(
+        r#"<failure message="unused dependencies: rand, serde" type="unused-dependencies">"#
+    ));
+
+    // Stale ignored entries of workspaces are noted too.
+    let workspace = base
+        .join("ignored-dep-workspace")
+        .to_string_lossy()
+        .into_owned();
+    assert!(report.contains(&format!(
+        "<system-out>futures at {workspace}/Cargo.toml:12:5 was marked as ignored, but no member \
+         needs it to be!</system-out>"
+    )));
+
+    assert_eq!(escape_xml(r#"<a & "b">"#), "&lt;a &amp; &quot;b&quot;&gt;");
+
+    Ok(())
+}
@@ -0,0 +1,296 @@
+//! A printer that will report the results as Markdown, e.g. to be posted as a comment on a pull
+//! request.
+//!
+//! Packages are grouped by workspace, with a table listing the findings of each workspace, and
+//! collapsible sections for the clean packages and the suggested `cargo-machete` metadata.
+
+use std::{
+    borrow::Cow,
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    DependencyKind, PackageAnalysis, WorkspaceAnalysis,
+    printers::{AnalyzedPaths, Printer},
+};
+
+pub struct MarkdownPrinter;
+
+/// The packages of a workspace, or those which aren't part of any.
+struct Group<'a> {
+    /// Canonical path to the workspace's manifest.
+    key: Option<PathBuf>,
+    workspace: Option<&'a WorkspaceAnalysis>,
+    packages: Vec<&'a PackageAnalysis>,
+}
+
+/// Escapes text for use in a table cell.
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+/// Groups the packages by workspace, in the order they've been found; packages which aren't part
+/// of any workspace come last.
+fn groups<'a>(
+    results: &'a [PackageAnalysis],
+    workspaces: &'a [WorkspaceAnalysis],
+) -> Vec<Group<'a>> {
+    let mut groups: Vec<Group<'a>> = workspaces
+        .iter()
+        .map(|workspace| Group {
+            key: Some(
+                std::fs::canonicalize(&workspace.manifest_path)
+                    .unwrap_or_else(|_| workspace.manifest_path.clone()),
+            ),
+            workspace: Some(workspace),
+            packages: Vec::new(),
+        })
+        .collect();
+    let mut standalone = Group {
+        key: None,
+        workspace: None,
+        packages: Vec::new(),
+    };
+
+    for package in results {
+        let Some(key) = &package.workspace_manifest_path else {
+            standalone.packages.push(package);
+            continue;
+        };
+        match groups
+            .iter_mut()
+            .find(|group| group.key.as_ref() == Some(key))
+        {
+            Some(group) => group.packages.push(package),
+            // The workspace's root is outside of the analyzed path.
+            None => groups.push(Group {
+                key: Some(key.clone()),
+                workspace: None,
+                packages: vec![package],
+            }),
+        }
+    }
+
+    if !standalone.packages.is_empty() {
+        groups.push(standalone);
+    }
+    groups
+}
+
+/// Formats the unused dependencies of a package, along with their table if it's not the plain
+/// `[dependencies]`.
+fn unused_cell(package: &PackageAnalysis) -> String {
+    let cell = package
+        .unused
+        .iter()
+        .map(|dep| match (dep.kind, &dep.target) {
+            (DependencyKind::Normal, None) => format!("`{}`", dep.name),
+            _ => format!("`{}` ({})", dep.name, dep.table_name()),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    escape_cell(&cell)
+}
+
+/// Returns the `[package.metadata.cargo-machete]` table ignoring the package's unused
+/// dependencies, without the used ones that are currently ignored.
+fn suggested_metadata(package: &PackageAnalysis) -> String {
+    let mut ignored = package
+        .ignored()
+        .iter()
+        .filter(|name| !package.ignored_used.iter().any(|dep| dep.name == **name))
+        .chain(package.unused.iter().map(|dep| &dep.name))
+        .map(|name| format!("{name:?}"))
+        .collect::<Vec<_>>();
+    ignored.sort();
+    ignored.dedup();
+
+    format!(
+        "[package.metadata.cargo-machete]\nignored = [{}]",
+        ignored.join(", ")
+    )
+}
+
+fn render(path: &Path, results: &[PackageAnalysis], workspaces: &[WorkspaceAnalysis]) -> String {
+    let location = match path.to_string_lossy() {
+        Cow::Borrowed(".") => Cow::from("this directory"),
+        pathstr => Cow::from(format!("`{pathstr}`")),
+    };
+
+    let mut md = String::new();
+    let _ = writeln!(md, "## Results for {location}\n");
+    let _ = writeln!(
+        md,
+        "{} package(s) analyzed, {} with unused dependencies, {} with ignored dependencies that \
+         are actually used.\n",
+        results.len(),
+        results
+            .iter()
+            .filter(|package| !package.unused.is_empty())
+            .count(),
+        results
+            .iter()
+            .filter(|package| !package.ignored_used.is_empty())
+            .count(),
+    );
+
+    for group in groups(results, workspaces) {
+        match (&group.workspace, &group.key) {
+            (Some(workspace), _) => {
+                let _ = writeln!(
+                    md,
+                    "### Workspace `{}`\n",
+                    workspace.manifest_path.to_string_lossy()
+                );
+            }
+            (None, Some(key)) => {
+                let _ = writeln!(md, "### Workspace `{}`\n", key.to_string_lossy());
+            }
+            (None, None) => md.push_str("### Packages outside of a workspace\n\n"),
+        }
+
+        if let Some(workspace) = group.workspace
+            && !workspace.unused.is_empty()
+        {
+            let names = workspace
+                .unused
+                .iter()
+                .map(|dep| format!("`{}`", dep.name))
+                .collect::<Vec<_>>();
+            let _ = writeln!(
+                md,
+                "Unused `[workspace.dependencies]`: {}\n",
+                names.join(", ")
+            );
+        }
+
+        if let Some(workspace) = group.workspace
+            && !workspace.ignored_used.is_empty()
+        {
+            let names = workspace
+                .ignored_used
+                .iter()
+                .map(|dep| format!("`{}`", dep.name))
+                .collect::<Vec<_>>();
+            let _ = writeln!(
+                md,
+                "Ignored in `[workspace.metadata.cargo-machete]`, but actually used: {}\n",
+                names.join(", ")
+            );
+        }
+
+        let (dirty, clean): (Vec<&PackageAnalysis>, Vec<_>) = group
+            .packages
+            .iter()
+            .copied()
+            .partition(|package| package.findings().next().is_some());
+
+        if dirty.is_empty() {
+            md.push_str("No findings in these packages.\n\n");
+        } else {
+            md.push_str(
+                "| Package | Manifest path | Unused dependencies | Ignored but used |\n\
+                 |---|---|---|---|\n",
+            );
+            for package in &dirty {
+                let ignored_used = package
+                    .ignored_used
+                    .iter()
+                    .map(|dep| format!("`{}`", dep.name))
+                    .collect::<Vec<_>>();
+                let _ = writeln!(
+                    md,
+                    "| `{}` | `{}` | {} | {} |",
+                    package.package_name,
+                    escape_cell(&package.manifest_path.to_string_lossy()),
+                    unused_cell(package),
+                    escape_cell(&ignored_used.join(", ")),
+                );
+            }
+            md.push('\n');
+
+            for package in &dirty {
+                let _ = writeln!(
+                    md,
+                    "<details>\n<summary>Suggested <code>cargo-machete</code> metadata for \
+                     <code>{}</code></summary>\n\n\
+                     If the unused dependencies are false positives, they can be ignored in \
+                     <code>{}</code>:\n\n\
+                     ```toml\n{}\n```\n\n</details>\n",
+                    package.package_name,
+                    package.manifest_path.to_string_lossy(),
+                    suggested_metadata(package)
+                );
+            }
+        }
+
+        if !clean.is_empty() {
+            let _ = writeln!(
+                md,
+                "<details>\n<summary>{} package(s) without findings</summary>\n",
+                clean.len()
+            );
+            for package in &clean {
+                let _ = writeln!(md, "- `{}`", package.package_name);
+            }
+            md.push_str("\n</details>\n\n");
+        }
+    }
+
+    md
+}
+
+impl Printer for MarkdownPrinter {
+    fn print_version(&self, version: &str) -> anyhow::Result<()> {
+        println!("{version}");
+        Ok(())
+    }
+
+    fn print_paths<'a>(&self, _paths: AnalyzedPaths<'a>) {
+        println!("# cargo-machete report\n");
+    }
+
+    fn print_results(
+        &self,
+        path: &Path,
+        results: &[PackageAnalysis],
+        workspaces: &[WorkspaceAnalysis],
+    ) -> anyhow::Result<()> {
+        print!("{}", render(path, results, workspaces));
+        Ok(())
+    }
+
+    fn print_tail(&self, _has_unused_dependencies: bool, _exit_status: i32) {
+        // Print nothing.
+    }
+}
+
+#[test]
+fn test_markdown_printer() -> anyhow::Result<()> {
+    let base = PathBuf::from(crate::TOP_LEVEL).join("integration-tests");
+    let analysis = crate::Analyzer::new().analyze_path(&base.join("ignored-dep"))?;
+    let md = render(&analysis.path, &analysis.packages, &analysis.workspaces);
+
+    assert!(md.contains("### Packages outside of a workspace"));
+    assert!(md.contains(&format!(
+        "| `ignored-dep` | `{}` | `rand` | `rand_core` |",
+        analysis.packages[0].manifest_path.to_string_lossy()
+    )));
+    // `log` stays ignored, `rand` is added, and `rand_core` is removed since it's used.
+    assert!(
+        md.contains(
+            "```toml\n[package.metadata.cargo-machete]\nignored = [\"log\", \"rand\"]\n```"
+        )
+    );
+
+    let analysis =
+        crate::Analyzer::new().analyze_path(&base.join("unused-workspace-dependencies"))?;
+    let md = render(&analysis.path, &analysis.packages, &analysis.workspaces);
+    assert!(md.contains("### Workspace `"));
+    assert!(md.contains("Unused `[workspace.dependencies]`: `rand`, `serde`"));
+    assert!(md.contains("<summary>1 package(s) without findings</summary>\n\n- `member`\n"));
+    assert!(!md.contains("| Package |"));
+
+    Ok(())
+}
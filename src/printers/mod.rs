@@ -1,9 +1,21 @@
+pub mod checkstyle;
+pub mod codeclimate;
+pub mod github;
 pub mod json;
+pub mod junit;
+pub mod markdown;
+pub mod ndjson;
+pub mod sarif;
 pub mod stdout;
 
 use std::path::{Path, PathBuf};
 
-use crate::search_unused::PackageAnalysis;
+use crate::{Finding, Location, PackageAnalysis, WorkspaceAnalysis};
+
+/// Rule for dependencies that are declared, but unused.
+const UNUSED_RULE_ID: &str = "unused-dependency";
+/// Rule for dependencies marked as ignored, but which are actually used.
+const IGNORED_USED_RULE_ID: &str = "ignored-dependency-used";
 
 /// Which paths are going to be analyzed by machete?
 pub enum AnalyzedPaths<'a> {
@@ -16,7 +28,207 @@ pub enum AnalyzedPaths<'a> {
     Paths(&'a [PathBuf]),
 }
 
+/// Formats the location of a dependency as ` at path:line:col`, so that editors and terminals can
+/// jump to it; or nothing if the location is unknown.
+fn at(manifest_path: &Path, location: Option<&Location>) -> String {
+    match location {
+        Some(location) => format!(
+            " at {}:{}:{}",
+            manifest_path.to_string_lossy(),
+            location.line,
+            location.column
+        ),
+        None => String::new(),
+    }
+}
+
+/// Formats the path to a manifest for reports read by other tools: with forward slashes, and
+/// without a leading `./`.
+fn relative_path(manifest_path: &Path) -> String {
+    let path = manifest_path.to_string_lossy().replace('\\', "/");
+    match path.strip_prefix("./") {
+        Some(stripped) => stripped.to_owned(),
+        None => path,
+    }
+}
+
+/// Formats the path to a workspace manifest relative to the root of the repository containing it,
+/// which tells workspaces apart without depending on the directory cargo-machete is run from.
+///
+/// Falls back to the absolute path of the manifest outside of a repository.
+fn workspace_relative_path(manifest_path: &Path) -> String {
+    let path = std::fs::canonicalize(manifest_path)
+        .or_else(|_| std::path::absolute(manifest_path))
+        .unwrap_or_else(|_| manifest_path.to_owned());
+    let relative = path
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.join(".git").exists())
+        .and_then(|root| path.strip_prefix(root).ok())
+        .unwrap_or(&path);
+    relative_path(relative)
+}
+
+/// A finding, described independently of the output format, for the reports listing all the
+/// findings one after the other.
+struct Issue<'a> {
+    rule_id: &'static str,
+    /// A short summary of the finding, e.g. for the title of an annotation.
+    title: &'static str,
+    manifest_path: &'a Path,
+    location: Option<&'a Location>,
+    message: String,
+    /// Identifies the finding across runs, even if lines move in the manifest.
+    fingerprint: String,
+}
+
+/// Hashes the given parts with 64-bit FNV-1a, which is stable across runs and platforms.
+fn fingerprint(parts: &[&str]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in parts.join("\0").bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{hash:016x}")
+}
+
+/// Lists all the findings, in order.
+fn issues<'a>(
+    results: &'a [PackageAnalysis],
+    workspaces: &'a [WorkspaceAnalysis],
+) -> Vec<Issue<'a>> {
+    let mut issues = Vec::new();
+
+    for analysis in results {
+        for finding in analysis.findings() {
+            issues.push(match finding {
+                Finding::Unused(dep) => Issue {
+                    rule_id: UNUSED_RULE_ID,
+                    title: "Unused dependency",
+                    manifest_path: &analysis.manifest_path,
+                    location: dep.location.as_ref(),
+                    message: format!(
+                        "`{}` is declared in [{}] of {}, but isn't used.",
+                        dep.name,
+                        dep.table_name(),
+                        analysis.package_name
+                    ),
+                    fingerprint: fingerprint(&[
+                        &analysis.package_name,
+                        &dep.table_name(),
+                        &dep.name,
+                    ]),
+                },
+                Finding::IgnoredButUsed(dep) => Issue {
+                    rule_id: IGNORED_USED_RULE_ID,
+                    title: "Ignored dependency is used",
+                    manifest_path: &analysis.manifest_path,
+                    location: dep.location.as_ref(),
+                    message: format!(
+                        "`{}` is marked as ignored in {}, but is actually used.",
+                        dep.name, analysis.package_name
+                    ),
+                    fingerprint: fingerprint(&[
+                        &analysis.package_name,
+                        "cargo-machete.ignored",
+                        &dep.name,
+                    ]),
+                },
+            });
+        }
+    }
+
+    for analysis in workspaces {
+        for dep in &analysis.unused {
+            issues.push(Issue {
+                rule_id: UNUSED_RULE_ID,
+                title: "Unused workspace dependency",
+                manifest_path: &analysis.manifest_path,
+                location: dep.location.as_ref(),
+                message: format!(
+                    "`{}` is declared in [workspace.dependencies], but isn't inherited by any \
+                     member.",
+                    dep.name
+                ),
+                // Workspaces don't have a name: tell them apart with their path instead.
+                fingerprint: fingerprint(&[
+                    &workspace_relative_path(&analysis.manifest_path),
+                    "workspace.dependencies",
+                    &dep.name,
+                ]),
+            });
+        }
+        for dep in &analysis.ignored_used {
+            issues.push(Issue {
+                rule_id: IGNORED_USED_RULE_ID,
+                title: "Unneeded ignored workspace dependency",
+                manifest_path: &analysis.manifest_path,
+                location: dep.location.as_ref(),
+                message: format!(
+                    "`{}` is marked as ignored in the workspace, but no member needs it to be.",
+                    dep.name
+                ),
+                fingerprint: fingerprint(&[
+                    &workspace_relative_path(&analysis.manifest_path),
+                    "workspace.metadata.cargo-machete.ignored",
+                    &dep.name,
+                ]),
+            });
+        }
+    }
+
+    issues
+}
+
+/// Analyzes the given directories of `integration-tests`, reporting the unneeded ignored entries of
+/// workspaces too, and prints the results with the printer.
+#[cfg(test)]
+fn print_fixtures(
+    printer: &dyn Printer,
+    fixtures: &[&str],
+) -> anyhow::Result<Vec<crate::PathAnalysis>> {
+    let base = PathBuf::from(crate::TOP_LEVEL).join("integration-tests");
+    let analyses = crate::Analyzer::new()
+        .paths(fixtures.iter().map(|fixture| base.join(fixture)))
+        .check_workspace_ignores(true)
+        .analyze()
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    for analysis in &analyses {
+        printer.print_results(&analysis.path, &analysis.packages, &analysis.workspaces)?;
+    }
+    Ok(analyses)
+}
+
+/// Escapes text for use in XML content or attributes.
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Keeps only the packages and workspaces with unused dependencies.
+fn with_unused<'a>(
+    results: &'a [PackageAnalysis],
+    workspaces: &'a [WorkspaceAnalysis],
+) -> (Vec<&'a PackageAnalysis>, Vec<&'a WorkspaceAnalysis>) {
+    (
+        results
+            .iter()
+            .filter(|analysis| !analysis.unused.is_empty())
+            .collect(),
+        workspaces
+            .iter()
+            .filter(|analysis| !analysis.unused.is_empty())
+            .collect(),
+    )
+}
+
 /// General trait to implement for a printer.
+///
+/// Custom printers can be implemented outside of this crate, to display the results of an
+/// [`Analyzer`](crate::Analyzer) in another way.
 pub trait Printer {
     /// Print the current version of the binary.
     fn print_version(&self, version: &str) -> anyhow::Result<()>;
@@ -28,12 +240,54 @@ pub trait Printer {
     ///
     /// Will be called for any base paths specified in [`AnalyzedPaths`], even for those which don't
     /// have unused dependencies.
-    fn print_results<'a>(
+    ///
+    /// `results` contains all the packages found in the path, including those without any
+    /// findings, and `workspaces` all the workspaces, including those without any unused
+    /// `[workspace.dependencies]` entries.
+    fn print_results(
         &self,
         path: &Path,
-        results: &'a [(PackageAnalysis, &'a PathBuf)],
+        results: &[PackageAnalysis],
+        workspaces: &[WorkspaceAnalysis],
     ) -> anyhow::Result<()>;
 
+    /// Returns a function printing the results of a single package as soon as it's been analyzed,
+    /// before [`Printer::print_results`] is called with all the packages of the path.
+    ///
+    /// The function is called from several threads at once. By default, there's none, and the
+    /// results are only printed once the whole path has been analyzed.
+    fn package_printer(&self) -> Option<&(dyn Fn(&PackageAnalysis) + Sync)> {
+        None
+    }
+
+    /// Print the errors which happened when analyzing a path: the manifests which couldn't be
+    /// analyzed, or the path itself if it couldn't be walked over.
+    ///
+    /// By default, errors are printed to stderr.
+    fn print_errors(&self, errors: &[(PathBuf, anyhow::Error)]) {
+        for (path, err) in errors {
+            eprintln!("error when handling {}: {:#}", path.display(), err);
+        }
+    }
+
     /// Print the tail of the analysis, usually a "done" message, and the false positive explainer.
-    fn print_tail(&self, has_unused_dependencies: bool);
+    ///
+    /// Formats producing a single document (e.g. JSON, SARIF or XML reports) accumulate the
+    /// results of all the analyzed paths in [`Printer::print_results`], and print the whole
+    /// document here.
+    ///
+    /// `exit_status` is the status cargo-machete is about to exit with: 0 if no unused
+    /// dependencies were found, 1 if some were, 2 if a directory couldn't be walked over, or the
+    /// results couldn't be printed or fixed.
+    fn print_tail(&self, has_unused_dependencies: bool, exit_status: i32);
+}
+
+#[test]
+fn test_fingerprint() {
+    // Reference values of 64-bit FNV-1a.
+    assert_eq!(fingerprint(&[""]), "cbf29ce484222325");
+    assert_eq!(fingerprint(&["a"]), "af63dc4c8601ec8c");
+
+    // Parts are separated, so that they can't be confused.
+    assert_ne!(fingerprint(&["ab", "c"]), fingerprint(&["a", "bc"]));
 }
@@ -0,0 +1,206 @@
+//! A printer that will stream the results as newline-delimited JSON, one record per line.
+//!
+//! Each package is printed as soon as it's been analyzed, so that results show up progressively
+//! on large workspaces. Workspaces and errors are printed once their path has been analyzed, and
+//! the output ends with a summary record. Records have the same fields as in the JSON output, and
+//! a `type` field telling them apart.
+
+use std::{
+    cell::Cell,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{
+    PackageAnalysis, WorkspaceAnalysis,
+    printers::{
+        AnalyzedPaths, Printer,
+        json::{CrateResults, JsonError, SCHEMA_VERSION, WorkspaceResults},
+    },
+};
+
+#[derive(Default)]
+pub struct NdjsonPrinter {
+    packages: Cell<usize>,
+    packages_with_unused: Cell<usize>,
+    workspaces_with_unused: Cell<usize>,
+    unused: Cell<usize>,
+    ignored_used: Cell<usize>,
+    errors: Cell<usize>,
+}
+
+/// A single line of the output.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record {
+    Package(CrateResults),
+    Workspace(WorkspaceResults),
+    Error(JsonError),
+    Summary(Summary),
+}
+
+/// The last line of the output.
+#[derive(Serialize, Debug, PartialEq)]
+struct Summary {
+    /// Version of the schema of the records, the same as for the JSON output.
+    schema_version: u32,
+    /// Number of analyzed packages.
+    packages: usize,
+    /// Number of packages with unused dependencies.
+    packages_with_unused: usize,
+    /// Number of workspaces with unused workspace dependencies.
+    workspaces_with_unused: usize,
+    /// Total number of unused dependencies, in packages and workspaces.
+    unused: usize,
+    /// Total number of dependencies marked as ignored, but actually used, in packages and
+    /// workspaces.
+    ignored_used: usize,
+    /// Number of manifests which couldn't be analyzed, and paths which couldn't be walked over.
+    errors: usize,
+    /// Exit status of cargo-machete: 0 if no unused dependencies were found, 1 if some were, 2 on
+    /// error.
+    exit_status: i32,
+}
+
+impl Record {
+    fn line(&self) -> String {
+        serde_json::to_string(self).expect("NDJSON record serialization")
+    }
+}
+
+fn print_package(analysis: &PackageAnalysis) {
+    println!("{}", Record::Package(CrateResults::new(analysis)).line());
+}
+
+/// Increments a counter.
+fn add(counter: &Cell<usize>, n: usize) {
+    counter.set(counter.get() + n);
+}
+
+impl NdjsonPrinter {
+    fn summary(&self, exit_status: i32) -> Summary {
+        Summary {
+            schema_version: SCHEMA_VERSION,
+            packages: self.packages.get(),
+            packages_with_unused: self.packages_with_unused.get(),
+            workspaces_with_unused: self.workspaces_with_unused.get(),
+            unused: self.unused.get(),
+            ignored_used: self.ignored_used.get(),
+            errors: self.errors.get(),
+            exit_status,
+        }
+    }
+
+    /// Returns the records to be printed once a whole path has been analyzed, and updates the
+    /// counters of the summary.
+    fn results_records(
+        &self,
+        results: &[PackageAnalysis],
+        workspaces: &[WorkspaceAnalysis],
+    ) -> Vec<Record> {
+        // Packages have already been printed, but they still count for the summary.
+        for analysis in results {
+            add(&self.packages, 1);
+            add(
+                &self.packages_with_unused,
+                usize::from(!analysis.unused.is_empty()),
+            );
+            add(&self.unused, analysis.unused.len());
+            add(&self.ignored_used, analysis.ignored_used.len());
+        }
+
+        workspaces
+            .iter()
+            .map(|analysis| {
+                add(
+                    &self.workspaces_with_unused,
+                    usize::from(!analysis.unused.is_empty()),
+                );
+                add(&self.unused, analysis.unused.len());
+                add(&self.ignored_used, analysis.ignored_used.len());
+                Record::Workspace(WorkspaceResults::new(analysis))
+            })
+            .collect()
+    }
+}
+
+impl Printer for NdjsonPrinter {
+    fn print_version(&self, version: &str) -> anyhow::Result<()> {
+        println!("{}", serde_json::json!({ "version": version }));
+        Ok(())
+    }
+
+    fn print_paths<'a>(&self, _paths: AnalyzedPaths<'a>) {
+        // Print nothing.
+    }
+
+    fn package_printer(&self) -> Option<&(dyn Fn(&PackageAnalysis) + Sync)> {
+        Some(&print_package)
+    }
+
+    fn print_results(
+        &self,
+        _path: &Path,
+        results: &[PackageAnalysis],
+        workspaces: &[WorkspaceAnalysis],
+    ) -> anyhow::Result<()> {
+        for record in self.results_records(results, workspaces) {
+            println!("{}", record.line());
+        }
+        Ok(())
+    }
+
+    fn print_errors(&self, errors: &[(PathBuf, anyhow::Error)]) {
+        for (path, err) in errors {
+            add(&self.errors, 1);
+            println!("{}", Record::Error(JsonError::new(path, err)).line());
+        }
+    }
+
+    fn print_tail(&self, _has_unused_dependencies: bool, exit_status: i32) {
+        println!("{}", Record::Summary(self.summary(exit_status)).line());
+    }
+}
+
+#[test]
+fn test_ndjson_printer() -> anyhow::Result<()> {
+    let analysis = crate::Analyzer::new().analyze_path(
+        &PathBuf::from(crate::TOP_LEVEL).join("./integration-tests/unused-workspace-dependencies"),
+    )?;
+
+    let line = Record::Package(CrateResults::new(&analysis.packages[0])).line();
+    let record: serde_json::Value = serde_json::from_str(&line)?;
+    assert_eq!(record["type"], "package");
+    assert_eq!(record["package_name"], "member");
+    assert!(!line.contains('\n'));
+
+    let printer = NdjsonPrinter::default();
+    let records = printer.results_records(&analysis.packages, &analysis.workspaces);
+    assert_eq!(records.len(), 1);
+    let record: serde_json::Value = serde_json::from_str(&records[0].line())?;
+    assert_eq!(record["type"], "workspace");
+    assert_eq!(record["unused"], serde_json::json!(["rand", "serde"]));
+
+    assert_eq!(
+        printer.summary(1),
+        Summary {
+            schema_version: SCHEMA_VERSION,
+            packages: 1,
+            packages_with_unused: 0,
+            workspaces_with_unused: 1,
+            unused: 2,
+            ignored_used: 0,
+            errors: 0,
+            exit_status: 1,
+        }
+    );
+
+    // Errors are counted too.
+    let missing = PathBuf::from("./this/does/not/exist");
+    let err = crate::Analyzer::new().analyze_path(&missing).err().unwrap();
+    printer.print_errors(&[(missing, err)]);
+    assert_eq!(printer.summary(2).errors, 1);
+
+    Ok(())
+}
@@ -0,0 +1,269 @@
+//! A printer that will report the results as a SARIF 2.1.0 log, for code-scanning dashboards.
+//!
+//! Each finding is a result pointing at the dependency's line in its Cargo.toml file, under one of
+//! the rules of the `cargo-machete` tool.
+//!
+//! See https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html
+
+use std::{cell::RefCell, path::Path};
+
+use serde::Serialize;
+
+use crate::{
+    Location, PackageAnalysis, WorkspaceAnalysis,
+    printers::{
+        AnalyzedPaths, IGNORED_USED_RULE_ID, Printer, UNUSED_RULE_ID, issues, relative_path,
+    },
+};
+
+#[derive(Default)]
+pub struct SarifPrinter {
+    results: RefCell<Vec<SarifResult>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifLog {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<Run>,
+}
+
+#[derive(Serialize)]
+struct Run {
+    tool: Tool,
+    results: Vec<SarifResult>,
+}
+
+#[derive(Serialize)]
+struct Tool {
+    driver: Driver,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Driver {
+    name: &'static str,
+    version: &'static str,
+    information_uri: &'static str,
+    rules: Vec<Rule>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Rule {
+    id: &'static str,
+    short_description: Message,
+    default_configuration: Configuration,
+}
+
+#[derive(Serialize)]
+struct Configuration {
+    level: &'static str,
+}
+
+#[derive(Serialize)]
+struct Message {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    rule_id: &'static str,
+    rule_index: usize,
+    level: &'static str,
+    message: Message,
+    locations: Vec<SarifLocation>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifLocation {
+    physical_location: PhysicalLocation,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PhysicalLocation {
+    artifact_location: ArtifactLocation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    region: Option<Region>,
+}
+
+#[derive(Serialize)]
+struct ArtifactLocation {
+    uri: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Region {
+    start_line: usize,
+    start_column: usize,
+}
+
+/// The rules, in the order of their indices.
+fn rules() -> Vec<Rule> {
+    vec![
+        Rule {
+            id: UNUSED_RULE_ID,
+            short_description: Message {
+                text: "A dependency is declared in a Cargo.toml file, but isn't used.".to_owned(),
+            },
+            default_configuration: Configuration { level: "warning" },
+        },
+        Rule {
+            id: IGNORED_USED_RULE_ID,
+            short_description: Message {
+                text: "A dependency is marked as ignored in the cargo-machete metadata, but is \
+                       actually used."
+                    .to_owned(),
+            },
+            default_configuration: Configuration { level: "note" },
+        },
+    ]
+}
+
+impl SarifResult {
+    fn new(
+        rule_id: &'static str,
+        text: String,
+        manifest_path: &Path,
+        at: Option<&Location>,
+    ) -> Self {
+        let (rule_index, level) = match rule_id {
+            UNUSED_RULE_ID => (0, "warning"),
+            _ => (1, "note"),
+        };
+
+        // URIs use forward slashes, and relative references shouldn't start with `./`.
+        let uri = relative_path(manifest_path);
+
+        Self {
+            rule_id,
+            rule_index,
+            level,
+            message: Message { text },
+            locations: vec![SarifLocation {
+                physical_location: PhysicalLocation {
+                    artifact_location: ArtifactLocation { uri },
+                    region: at.map(|at| Region {
+                        start_line: at.line,
+                        start_column: at.column,
+                    }),
+                },
+            }],
+        }
+    }
+}
+
+impl SarifPrinter {
+    fn log(&self) -> SarifLog {
+        SarifLog {
+            schema: "https://json.schemastore.org/sarif-2.1.0.json",
+            version: "2.1.0",
+            runs: vec![Run {
+                tool: Tool {
+                    driver: Driver {
+                        name: "cargo-machete",
+                        version: env!("CARGO_PKG_VERSION"),
+                        information_uri: "https://github.com/bnjbvr/cargo-machete",
+                        rules: rules(),
+                    },
+                },
+                results: self.results.take(),
+            }],
+        }
+    }
+}
+
+impl Printer for SarifPrinter {
+    fn print_version(&self, version: &str) -> anyhow::Result<()> {
+        println!("{version}");
+        Ok(())
+    }
+
+    fn print_paths<'a>(&self, _paths: AnalyzedPaths<'a>) {
+        // Print nothing.
+    }
+
+    fn print_results(
+        &self,
+        _path: &Path,
+        results: &[PackageAnalysis],
+        workspaces: &[WorkspaceAnalysis],
+    ) -> anyhow::Result<()> {
+        self.results
+            .borrow_mut()
+            .extend(issues(results, workspaces).into_iter().map(|issue| {
+                SarifResult::new(
+                    issue.rule_id,
+                    issue.message,
+                    issue.manifest_path,
+                    issue.location,
+                )
+            }));
+        Ok(())
+    }
+
+    fn print_tail(&self, _has_unused_dependencies: bool, _exit_status: i32) {
+        println!(
+            "{}",
+            serde_json::to_string(&self.log()).expect("SARIF log serialization")
+        );
+    }
+}
+
+#[test]
+fn test_sarif_printer() -> anyhow::Result<()> {
+    let printer = SarifPrinter::default();
+    super::print_fixtures(&printer, &["ignored-dep", "ignored-dep-workspace"])?;
+
+    let log = serde_json::to_value(printer.log())?;
+    assert_eq!(log["version"], "2.1.0");
+
+    let run = &log["runs"][0];
+    assert_eq!(run["tool"]["driver"]["rules"][0]["id"], UNUSED_RULE_ID);
+    assert_eq!(
+        run["tool"]["driver"]["rules"][1]["id"],
+        IGNORED_USED_RULE_ID
+    );
+
+    // The results of the package come first, then those of the workspace's member and of the
+    // workspace itself.
+    let results = run["results"].as_array().unwrap();
+    assert_eq!(results.len(), 6);
+
+    assert_eq!(results[0]["ruleId"], UNUSED_RULE_ID);
+    assert_eq!(results[0]["ruleIndex"], 0);
+    let location = &results[0]["locations"][0]["physicalLocation"];
+    assert!(
+        location["artifactLocation"]["uri"]
+            .as_str()
+            .unwrap()
+            .ends_with("integration-tests/ignored-dep/Cargo.toml")
+    );
+    // `rand = "0.9.3"`
+    assert_eq!(location["region"]["startLine"], 11);
+    assert_eq!(location["region"]["startColumn"], 1);
+
+    assert_eq!(results[1]["ruleId"], IGNORED_USED_RULE_ID);
+    assert_eq!(results[1]["ruleIndex"], 1);
+    // `rand_core = "0.6.3"`
+    assert_eq!(
+        results[1]["locations"][0]["physicalLocation"]["region"]["startLine"],
+        12
+    );
+
+    // Stale ignored entries of workspaces are reported too.
+    assert_eq!(results[4]["ruleId"], UNUSED_RULE_ID);
+    assert_eq!(results[5]["ruleId"], IGNORED_USED_RULE_ID);
+    // `"futures"` in the `ignored` list.
+    let region = &results[5]["locations"][0]["physicalLocation"]["region"];
+    assert_eq!(region["startLine"], 12);
+    assert_eq!(region["startColumn"], 5);
+
+    Ok(())
+}
@@ -1,20 +1,33 @@
-//! A printer that will print everything to stdout.
+//! A printer that will print everything to stdout, or to stderr if stdout is already used for
+//! something else, like the diff of `--dry-run`.
 //!
 //! Errors will be reported to stderr.
 
-use std::{
-    borrow::Cow,
-    path::{Path, PathBuf},
-};
+use std::{borrow::Cow, path::Path};
 
 use crate::{
-    printers::{AnalyzedPaths, Printer},
-    search_unused::PackageAnalysis,
+    DependencyKind, Finding, PackageAnalysis, WorkspaceAnalysis,
+    printers::{AnalyzedPaths, Printer, at, with_unused},
 };
 
 pub struct StdoutPrinter {
     pub quiet: bool,
     pub with_metadata: bool,
+    pub to_stderr: bool,
+}
+
+/// Like `println!`, but to stderr if the printer says so.
+macro_rules! out {
+    ($printer:expr) => {
+        out!($printer, "")
+    };
+    ($printer:expr, $($arg:tt)*) => {
+        if $printer.to_stderr {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
 }
 
 impl Printer for StdoutPrinter {
@@ -32,10 +45,14 @@ impl Printer for StdoutPrinter {
 
         match paths {
             AnalyzedPaths::CurrentDir => {
-                println!("Analyzing dependencies of crates in this directory...");
+                out!(
+                    self,
+                    "Analyzing dependencies of crates in this directory..."
+                );
             }
             AnalyzedPaths::Paths(path_bufs) => {
-                println!(
+                out!(
+                    self,
                     "Analyzing dependencies of crates in {}...",
                     path_bufs
                         .iter()
@@ -47,45 +64,95 @@ impl Printer for StdoutPrinter {
         }
     }
 
-    fn print_results<'a>(
+    fn print_results(
         &self,
         path: &Path,
-        results: &'a [(PackageAnalysis, &'a PathBuf)],
+        results: &[PackageAnalysis],
+        workspaces: &[WorkspaceAnalysis],
     ) -> anyhow::Result<()> {
+        let (results, workspaces) = with_unused(results, workspaces);
+
         let location = match path.to_string_lossy() {
             Cow::Borrowed(".") => Cow::from("this directory"),
             pathstr => pathstr,
         };
 
-        if results.is_empty() {
+        if results.is_empty() && workspaces.is_empty() {
             if !self.quiet {
-                println!(
+                out!(
+                    self,
                     "cargo-machete didn't find any unused dependencies in {location}. Good job!"
                 );
             }
             return Ok(());
         }
 
-        println!("cargo-machete found the following unused dependencies in {location}:");
-        for (analysis, path) in results {
-            println!("{} -- {}:", analysis.package_name, path.to_string_lossy());
-            for dep in &analysis.unused {
-                println!("\t{dep}");
+        out!(
+            self,
+            "cargo-machete found the following unused dependencies in {location}:"
+        );
+        for analysis in results {
+            out!(
+                self,
+                "{} -- {}:",
+                analysis.package_name,
+                analysis.manifest_path.to_string_lossy()
+            );
+            let path = &analysis.manifest_path;
+            for finding in analysis.findings() {
+                match finding {
+                    Finding::Unused(dep) => {
+                        let at = at(path, dep.location.as_ref());
+                        match (dep.kind, &dep.target) {
+                            (DependencyKind::Normal, None) => out!(self, "\t{}{at}", dep.name),
+                            _ => out!(self, "\t{} (in [{}]){at}", dep.name, dep.table_name()),
+                        }
+                    }
+                    Finding::IgnoredButUsed(dep) => {
+                        out!(
+                            self,
+                            "\t⚠️  {}{} was marked as ignored, but is actually used!",
+                            dep.name,
+                            at(path, dep.location.as_ref())
+                        );
+                    }
+                }
             }
+        }
 
+        for analysis in workspaces {
+            out!(
+                self,
+                "workspace -- {}:",
+                analysis.manifest_path.to_string_lossy()
+            );
+            for dep in &analysis.unused {
+                out!(
+                    self,
+                    "\t{} (in [workspace.dependencies]){}",
+                    dep.name,
+                    at(&analysis.manifest_path, dep.location.as_ref())
+                );
+            }
             for dep in &analysis.ignored_used {
-                println!("\t⚠️  {dep} was marked as ignored, but is actually used!");
+                out!(
+                    self,
+                    "\t⚠️  {}{} was marked as ignored, but is actually used!",
+                    dep.name,
+                    at(&analysis.manifest_path, dep.location.as_ref())
+                );
             }
         }
 
-        println!();
+        out!(self);
 
         Ok(())
     }
 
-    fn print_tail(&self, has_unused_dependencies: bool) {
+    fn print_tail(&self, has_unused_dependencies: bool, _exit_status: i32) {
         if has_unused_dependencies {
-            println!(
+            out!(
+                self,
                 r#"If you believe cargo-machete has detected an unused dependency incorrectly, you can add the dependency to the list of dependencies to ignore in the `[package.metadata.cargo-machete]` section of the appropriate Cargo.toml.
 
 For example:
@@ -96,14 +163,15 @@ ignored = ["prost"]
             );
 
             if !self.with_metadata {
-                println!(
+                out!(
+                    self,
                     "You can also try running it with the `--with-metadata` flag for better accuracy, though this may modify your Cargo.lock files."
                 );
             }
         }
 
         if !self.quiet {
-            println!("Done!");
+            out!(self, "Done!");
         }
     }
 }
@@ -1,3 +1,4 @@
+use aho_corasick::AhoCorasick;
 use cargo_metadata::CargoOpt;
 use grep::{
     matcher::LineTerminator,
@@ -8,18 +9,28 @@ use log::{debug, trace, warn};
 use meta::MetadataFields;
 use rayon::prelude::*;
 use std::{
-    collections::{BTreeMap, HashSet},
-    error::{self, Error},
+    collections::{BTreeMap, BTreeSet, HashSet},
+    error,
     path::{Path, PathBuf},
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
 };
 use walkdir::WalkDir;
 
 #[cfg(test)]
 use crate::TOP_LEVEL;
-use crate::UseCargoMetadata;
+use crate::{
+    SearchBackend, UseCargoMetadata,
+    location::{Location, ManifestLocator},
+};
 
 use self::meta::PackageMetadata;
 
+mod lexer;
+mod syntax;
+
 mod meta {
     use std::collections::BTreeMap;
 
@@ -31,7 +42,7 @@ mod meta {
         pub cargo_machete: Option<MetadataFields>,
     }
 
-    #[derive(Serialize, Deserialize)]
+    #[derive(Clone, Serialize, Deserialize)]
     pub struct MetadataFields {
         /// Crates triggering false positives in `cargo-machete`, which should not be reported as
         /// unused.
@@ -47,12 +58,120 @@ mod meta {
     }
 }
 
-pub(crate) struct PackageAnalysis {
+/// The kind of a dependency, i.e. the section of the Cargo.toml it's declared in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DependencyKind {
+    /// A dependency declared in `[dependencies]`.
+    Normal,
+    /// A dependency declared in `[dev-dependencies]`, used only by tests, benchmarks, examples
+    /// and doc-tests.
+    Development,
+    /// A dependency declared in `[build-dependencies]`, used only by the build script.
+    Build,
+}
+
+impl DependencyKind {
+    /// Name of the Cargo.toml table in which dependencies of this kind are declared.
+    pub fn table_name(self) -> &'static str {
+        match self {
+            Self::Normal => "dependencies",
+            Self::Development => "dev-dependencies",
+            Self::Build => "build-dependencies",
+        }
+    }
+
+    /// Full name of the Cargo.toml table in which dependencies of this kind are declared for the
+    /// given target, e.g. `dev-dependencies` or `target.'cfg(unix)'.dependencies`.
+    pub(crate) fn full_table_name(self, target: Option<&str>) -> String {
+        match target {
+            None => self.table_name().to_owned(),
+            Some(target)
+                if target
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') =>
+            {
+                format!("target.{target}.{}", self.table_name())
+            }
+            Some(target) => format!("target.'{target}'.{}", self.table_name()),
+        }
+    }
+}
+
+/// A dependency that has been found unused by the analysis.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct UnusedDependency {
+    /// The name of the dependency, as the key in its Cargo.toml dependency table.
+    pub name: String,
+    /// Which kind of dependency this is.
+    pub kind: DependencyKind,
+    /// For a platform-specific dependency, the target it's declared for: either a target triple
+    /// (e.g. `x86_64-pc-windows-msvc`) or a `cfg()` expression (e.g. `cfg(unix)`).
+    pub target: Option<String>,
+    /// Where the dependency is declared in the Cargo.toml file, if it could be found.
+    pub location: Option<Location>,
+}
+
+impl UnusedDependency {
+    /// Full name of the Cargo.toml table in which this dependency is declared, e.g.
+    /// `dev-dependencies` or `target.'cfg(unix)'.dependencies`.
+    pub fn table_name(&self) -> String {
+        self.kind.full_table_name(self.target.as_deref())
+    }
+}
+
+/// The key of a dependency in a Cargo.toml file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DependencyKey {
+    /// The name of the dependency, as the key in its Cargo.toml dependency table.
+    pub name: String,
+    /// Where the dependency is declared in the Cargo.toml file, if it could be found.
+    pub location: Option<Location>,
+}
+
+/// Result of the analysis of a package.
+#[non_exhaustive]
+pub struct PackageAnalysis {
     metadata: Option<cargo_metadata::Metadata>,
-    pub manifest: cargo_toml::Manifest<meta::PackageMetadata>,
+    pub(crate) manifest: cargo_toml::Manifest<meta::PackageMetadata>,
+    /// Path to the package's Cargo.toml file.
+    pub manifest_path: PathBuf,
     pub package_name: String,
-    pub unused: Vec<String>,
-    pub ignored_used: Vec<String>,
+    /// Dependencies that are declared but unused, and not marked as ignored.
+    pub unused: Vec<UnusedDependency>,
+    /// Dependencies marked as ignored, but which are actually used.
+    pub ignored_used: Vec<DependencyKey>,
+    /// Canonical path to the workspace manifest, if the package is part of a workspace.
+    pub(crate) workspace_manifest_path: Option<PathBuf>,
+    /// Names of the dependencies inherited from the workspace (with `dep.workspace = true`).
+    pub(crate) inherited_dependencies: BTreeSet<String>,
+    /// Names of the dependencies that are unused, but only marked as ignored in the workspace's
+    /// `[workspace.metadata.cargo-machete]` table.
+    pub(crate) ignored_by_workspace: BTreeSet<String>,
+}
+
+/// A single finding of the analysis of a package.
+#[derive(Clone, Copy, Debug)]
+pub enum Finding<'a> {
+    /// A dependency that's declared but unused.
+    Unused(&'a UnusedDependency),
+    /// A dependency marked as ignored, which is actually used.
+    IgnoredButUsed(&'a DependencyKey),
+}
+
+/// Result of the analysis of a workspace's `[workspace.dependencies]` table.
+#[non_exhaustive]
+pub struct WorkspaceAnalysis {
+    /// Path to the workspace's Cargo.toml file.
+    pub manifest_path: PathBuf,
+    /// Workspace dependencies that no member inherits.
+    pub unused: Vec<DependencyKey>,
+    /// Dependencies marked as ignored in the `[workspace.metadata.cargo-machete]` table, which
+    /// neither a member nor the `[workspace.dependencies]` table needs to ignore. They're located
+    /// at their entry in the `ignored` list. Only filled in if asked for, with
+    /// [`crate::Analyzer::check_workspace_ignores`].
+    pub ignored_used: Vec<DependencyKey>,
 }
 
 impl PackageAnalysis {
@@ -77,11 +196,45 @@ impl PackageAnalysis {
         Ok(Self {
             metadata,
             manifest,
+            manifest_path: cargo_path.to_owned(),
             package_name,
             unused: Vec::default(),
             ignored_used: Vec::default(),
+            workspace_manifest_path: None,
+            inherited_dependencies: BTreeSet::default(),
+            ignored_by_workspace: BTreeSet::default(),
         })
     }
+
+    /// All the findings of the analysis: unused dependencies first, then ignored dependencies
+    /// which are actually used.
+    pub fn findings(&self) -> impl Iterator<Item = Finding<'_>> {
+        self.unused
+            .iter()
+            .map(Finding::Unused)
+            .chain(self.ignored_used.iter().map(Finding::IgnoredButUsed))
+    }
+
+    /// Canonical path to the Cargo.toml file of the package's workspace, if it's part of one.
+    pub fn workspace_manifest_path(&self) -> Option<&Path> {
+        self.workspace_manifest_path.as_deref()
+    }
+
+    /// Dependencies marked as ignored in the `[package.metadata.cargo-machete]` table of the
+    /// package's Cargo.toml file.
+    pub fn ignored(&self) -> &[String] {
+        self.manifest
+            .package
+            .as_ref()
+            .and_then(|package| package.metadata.as_ref()?.cargo_machete.as_ref())
+            .map_or(&[], |meta| &meta.ignored)
+    }
+
+    /// Names of the unused dependencies, regardless of their kind.
+    #[cfg(test)]
+    fn unused_names(&self) -> Vec<&str> {
+        self.unused.iter().map(|dep| dep.name.as_str()).collect()
+    }
 }
 
 fn make_line_regexp(name: &str) -> String {
@@ -174,104 +327,452 @@ fn collect_paths(dir_path: &Path, analysis: &PackageAnalysis) -> Vec<PathBuf> {
     paths
 }
 
-/// Performs search of the given crate name with the following strategy: first try to use the line
-/// matcher, then the multiline matcher if the line matcher failed.
+/// Returns all the paths to the Rust source files of the build script of a crate contained at the
+/// given path: the build script itself, and the modules and files it includes.
+///
+/// Returns an empty list if the crate doesn't have a build script.
+fn collect_build_script_paths(dir_path: &Path, analysis: &PackageAnalysis) -> Vec<PathBuf> {
+    let Some(build_script) = analysis
+        .manifest
+        .package
+        .as_ref()
+        .and_then(|package| package.build.as_ref()?.as_path())
+    else {
+        trace!("no build script found");
+        return Vec::new();
+    };
+
+    let mut paths = Vec::new();
+
+    // Files to visit, along with a boolean indicating whether the file's submodules live in the
+    // same directory (i.e. it's the build script itself, a `mod.rs` file or an included file).
+    let mut to_visit = vec![(dir_path.join(build_script), true)];
+
+    while let Some((path, owns_directory)) = to_visit.pop() {
+        if paths.contains(&path) {
+            continue;
+        }
+
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) => {
+                eprintln!("{}: {}", path.display(), err);
+                continue;
+            }
+        };
+
+        let parent = path.parent().unwrap_or(dir_path).to_owned();
+        let mod_dir = match path.file_stem() {
+            Some(stem) if !owns_directory => parent.join(stem),
+            _ => parent.clone(),
+        };
+
+        // The value of the last `#[path = "..."]` attribute, which applies to the next module.
+        let mut path_attribute = None;
+
+        for line in content.lines() {
+            let line = line.trim();
+
+            if let Some(attr_path) = parse_path_attribute(line) {
+                path_attribute = Some(parent.join(attr_path));
+            } else if let Some(name) = parse_mod_declaration(line) {
+                let candidates = match path_attribute.take() {
+                    Some(attr_path) => vec![(attr_path, true)],
+                    None => vec![
+                        (mod_dir.join(format!("{name}.rs")), false),
+                        (mod_dir.join(name).join("mod.rs"), true),
+                    ],
+                };
+                if let Some(found) = candidates.into_iter().find(|(path, _)| path.is_file()) {
+                    to_visit.push(found);
+                }
+            } else if let Some(included) = parse_include(line) {
+                to_visit.push((parent.join(included), true));
+            }
+        }
+
+        paths.push(path);
+    }
+
+    trace!("found build script paths: {paths:?}");
+
+    paths
+}
+
+/// Parses an out-of-line module declaration (e.g. `pub(crate) mod foo;`), returning the module's
+/// name.
+fn parse_mod_declaration(line: &str) -> Option<&str> {
+    let line = match line.strip_prefix("pub") {
+        Some(rest) if rest.starts_with('(') => &rest[rest.find(')')? + 1..],
+        Some(rest) => rest,
+        None => line,
+    };
+    let name = line
+        .trim_start()
+        .strip_prefix("mod ")?
+        .strip_suffix(';')?
+        .trim();
+    name.chars()
+        .all(|c| c.is_alphanumeric() || c == '_')
+        .then_some(name)
+}
+
+/// Parses a `#[path = "..."]` attribute, returning the path.
+fn parse_path_attribute(line: &str) -> Option<&str> {
+    let value = line
+        .strip_prefix("#[path")?
+        .trim_start()
+        .strip_prefix('=')?;
+    value
+        .trim()
+        .strip_suffix(']')?
+        .trim()
+        .strip_prefix('"')?
+        .strip_suffix('"')
+}
+
+/// Parses an `include!("...")` macro invocation with a literal path, returning the path.
+fn parse_include(line: &str) -> Option<&str> {
+    let start = line.find("include!(\"")? + "include!(\"".len();
+    let len = line[start..].find('"')?;
+    Some(&line[start..start + len])
+}
+
+/// The regular expressions looking for a crate name: a line matcher, and a multiline matcher for
+/// the uses the line matcher can't catch.
 ///
 /// Splitting the single line matcher from the multiline matcher makes maintenance of the regular
 /// expressions simpler (oh well), and likely faster too since most use statements will be caught
 /// by the single line matcher.
-struct Search {
-    line_matcher: RegexMatcher,
+///
+/// Compiling them is what takes the most time when looking for many crates, so each of them is
+/// only compiled the first time it's needed: a crate is usually only looked for in a handful of
+/// files, and most uses are found by the line matcher.
+struct RegexMatchers {
+    /// The crate name, as used in the code (snake case).
+    crate_name: String,
+    line_matcher: OnceLock<Result<RegexMatcher, String>>,
+    multiline_matcher: OnceLock<Result<RegexMatcher, String>>,
+}
+
+impl RegexMatchers {
+    fn new(crate_name: &str) -> Self {
+        assert!(!crate_name.contains('-'));
+
+        Self {
+            crate_name: crate_name.to_owned(),
+            line_matcher: OnceLock::new(),
+            multiline_matcher: OnceLock::new(),
+        }
+    }
+
+    fn line_matcher(&self) -> anyhow::Result<&RegexMatcher> {
+        self.line_matcher
+            .get_or_init(|| {
+                RegexMatcher::new_line_matcher(&make_line_regexp(&self.crate_name))
+                    .map_err(|err| err.to_string())
+            })
+            .as_ref()
+            .map_err(|err| anyhow::anyhow!("when building line pattern: {err}"))
+    }
+
+    fn multiline_matcher(&self) -> anyhow::Result<&RegexMatcher> {
+        self.multiline_matcher
+            .get_or_init(|| {
+                RegexMatcherBuilder::new()
+                    .multi_line(true)
+                    .build(&make_multiline_regexp(&self.crate_name))
+                    .map_err(|err| err.to_string())
+            })
+            .as_ref()
+            .map_err(|err| anyhow::anyhow!("when building complex pattern: {err}"))
+    }
+}
+
+/// Performs search of crate names with the following strategy: first try to use the line matcher,
+/// then the multiline matcher if the line matcher failed.
+///
+/// The code must have gone through [`lexer::blank_comments_and_strings`] beforehand, so that
+/// matches in comments and string literals are skipped.
+#[derive(Clone)]
+struct RegexSearch {
     line_searcher: Searcher,
-    multiline_matcher: RegexMatcher,
     multiline_searcher: Searcher,
     sink: StopAfterFirstMatch,
 }
 
-impl Search {
-    fn new(crate_name: &str) -> anyhow::Result<Self> {
-        assert!(!crate_name.contains('-'));
-
-        let line_matcher = RegexMatcher::new_line_matcher(&make_line_regexp(crate_name))?;
+impl RegexSearch {
+    fn new() -> Self {
         let line_searcher = SearcherBuilder::new()
             .binary_detection(BinaryDetection::quit(b'\x00'))
             .line_terminator(LineTerminator::byte(b'\n'))
             .line_number(false)
             .build();
 
-        let multiline_matcher = RegexMatcherBuilder::new()
-            .multi_line(true)
-            .build(&make_multiline_regexp(crate_name))?;
         let multiline_searcher = SearcherBuilder::new()
             .binary_detection(BinaryDetection::quit(b'\x00'))
             .multi_line(true)
             .line_number(false)
             .build();
 
-        // Sanity-check: the matcher must allow multi-line searching.
-        debug_assert!(multiline_searcher.multi_line_with_matcher(&multiline_matcher));
-
         let sink = StopAfterFirstMatch::new();
 
-        Ok(Self {
-            line_matcher,
+        Self {
             line_searcher,
-            multiline_matcher,
             multiline_searcher,
             sink,
-        })
+        }
     }
 
-    fn try_singleline_then_multiline<
-        F: FnMut(&mut Searcher, &RegexMatcher, &mut StopAfterFirstMatch) -> Result<(), Box<dyn Error>>,
-    >(
-        &mut self,
-        mut func: F,
-    ) -> anyhow::Result<bool> {
-        match func(&mut self.line_searcher, &self.line_matcher, &mut self.sink) {
-            Ok(()) => {
-                if self.sink.found {
-                    return Ok(true);
+    fn search_code(&mut self, matchers: &RegexMatchers, code: &[u8]) -> anyhow::Result<bool> {
+        self.sink.found = false;
+        self.line_searcher
+            .search_slice(matchers.line_matcher()?, code, &mut self.sink)
+            .map_err(|err| anyhow::anyhow!("when searching with line pattern: {err}"))?;
+        if self.sink.found {
+            return Ok(true);
+        }
+
+        // Single line matcher didn't work, try the multiline matcher now.
+        let multiline_matcher = matchers.multiline_matcher()?;
+        // Sanity-check: the matcher must allow multi-line searching.
+        debug_assert!(
+            self.multiline_searcher
+                .multi_line_with_matcher(multiline_matcher)
+        );
+        self.multiline_searcher
+            .search_slice(multiline_matcher, code, &mut self.sink)
+            .map_err(|err| anyhow::anyhow!("when searching with complex pattern: {err}"))?;
+        Ok(self.sink.found)
+    }
+}
+
+/// A crate to look for in source files.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct SearchedCrate {
+    /// The crate name, as used in the code (snake case).
+    name: String,
+    /// Should uses in doc comments count? Those may contain doc-tests, which can make use of
+    /// dev-dependencies.
+    match_doc_comments: bool,
+}
+
+/// A crate to look for, along with the state needed to confirm its use.
+///
+/// It's shared by all the threads searching files.
+struct CrateSearch {
+    /// Lowercased crate name, since the regular expressions match the crate name
+    /// case-insensitively.
+    lowercase_name: String,
+    match_doc_comments: bool,
+    regex: RegexMatchers,
+    /// Has the crate already been found in a file? Then there's no need to look for it again.
+    found: AtomicBool,
+}
+
+/// Performs search of several crate names at once in source files, so that each file is read only
+/// once.
+///
+/// A single Aho-Corasick automaton looking for all the crate names prefilters the candidate crates
+/// for a given file; then each candidate is confirmed with the selected backend:
+///
+/// - with regular expressions, after comments and string literals have been blanked out,
+/// - or by parsing the file, and looking at the roots of the paths it contains. This is slower,
+///   but more accurate. Files that can't be parsed are searched with the regular expressions.
+///
+/// Crates found in a file aren't looked for in the next ones. Clones of a search share the crates,
+/// so that they can search different files in parallel.
+#[derive(Clone)]
+struct Search {
+    backend: SearchBackend,
+    crates: Arc<[CrateSearch]>,
+    prefilter: AhoCorasick,
+    /// Indices of the crates corresponding to each pattern of the prefilter.
+    crates_by_pattern: Arc<[Vec<usize>]>,
+    regex: RegexSearch,
+}
+
+impl Search {
+    fn new(crates: &[SearchedCrate], backend: SearchBackend) -> anyhow::Result<Self> {
+        let crates = crates
+            .iter()
+            .map(|searched| CrateSearch {
+                lowercase_name: searched.name.to_lowercase(),
+                match_doc_comments: searched.match_doc_comments,
+                regex: RegexMatchers::new(&searched.name),
+                found: AtomicBool::new(false),
+            })
+            .collect::<Arc<[_]>>();
+
+        let mut patterns: Vec<&str> = Vec::new();
+        let mut crates_by_pattern: Vec<Vec<usize>> = Vec::new();
+        for (i, krate) in crates.iter().enumerate() {
+            match patterns.iter().position(|p| *p == krate.lowercase_name) {
+                Some(pattern) => crates_by_pattern[pattern].push(i),
+                None => {
+                    patterns.push(&krate.lowercase_name);
+                    crates_by_pattern.push(vec![i]);
                 }
-                // Single line matcher didn't work, try the multiline matcher now.
-                func(
-                    &mut self.multiline_searcher,
-                    &self.multiline_matcher,
-                    &mut self.sink,
-                )
-                .map_err(|err| anyhow::anyhow!("when searching with complex pattern: {err}"))
-                .map(|()| self.sink.found)
             }
-            Err(err) => anyhow::bail!("when searching with line pattern: {err}"),
         }
-    }
 
-    fn search_path(&mut self, path: &Path) -> anyhow::Result<bool> {
-        self.try_singleline_then_multiline(|searcher, matcher, sink| {
-            searcher.search_path(matcher, path, sink)
+        let prefilter = AhoCorasick::builder()
+            .ascii_case_insensitive(true)
+            .build(&patterns)?;
+
+        Ok(Self {
+            backend,
+            crates,
+            prefilter,
+            crates_by_pattern: crates_by_pattern.into(),
+            regex: RegexSearch::new(),
         })
     }
 
+    /// Returns the indices of the crates used by the file at the given path, which haven't been
+    /// found in another file yet.
+    fn search_path(&mut self, path: &Path) -> anyhow::Result<Vec<usize>> {
+        let content = std::fs::read(path)?;
+        self.search_bytes(&content)
+    }
+
+    /// Returns the indices of the crates used by the given source code, which haven't been found
+    /// in another file yet.
+    fn search_bytes(&mut self, content: &[u8]) -> anyhow::Result<Vec<usize>> {
+        // Only the crates whose name appears somewhere in the file may be used by it.
+        let candidates = self
+            .prefilter
+            .find_overlapping_iter(content)
+            .flat_map(|mat| &self.crates_by_pattern[mat.pattern().as_usize()])
+            .copied()
+            .filter(|&i| !self.crates[i].found.load(Ordering::Relaxed))
+            .collect::<BTreeSet<_>>();
+
+        if candidates.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let match_doc_comments = candidates
+            .iter()
+            .any(|&i| self.crates[i].match_doc_comments);
+
+        if self.backend == SearchBackend::Syntax {
+            // Not valid UTF-8, or can't be tokenized: let the regex searcher deal with it.
+            if let Ok(content) = std::str::from_utf8(content)
+                && let Some(roots) = syntax::crate_roots(content, match_doc_comments)
+            {
+                return Ok(candidates
+                    .into_iter()
+                    .filter(|&i| {
+                        let krate = &self.crates[i];
+                        let found = roots.code.contains(&krate.lowercase_name)
+                            || (krate.match_doc_comments
+                                && roots.doc_tests.contains(&krate.lowercase_name));
+                        if found {
+                            krate.found.store(true, Ordering::Relaxed);
+                        }
+                        found
+                    })
+                    .collect());
+            }
+        }
+
+        let code = lexer::blank_comments_and_strings(content, false);
+        let code_and_docs =
+            match_doc_comments.then(|| lexer::blank_comments_and_strings(content, true));
+        let attribute_modules = lexer::attribute_module_names(&code);
+
+        let mut found = Vec::new();
+        for i in candidates {
+            let krate = &self.crates[i];
+            let code = match &code_and_docs {
+                Some(code_and_docs) if krate.match_doc_comments => code_and_docs,
+                _ => &code,
+            };
+            if attribute_modules.contains(&krate.lowercase_name)
+                || self.regex.search_code(&krate.regex, code)?
+            {
+                krate.found.store(true, Ordering::Relaxed);
+                found.push(i);
+            }
+        }
+        Ok(found)
+    }
+
     #[cfg(test)]
-    fn search_string(&mut self, s: &str) -> anyhow::Result<bool> {
-        self.try_singleline_then_multiline(|searcher, matcher, sink| {
-            searcher.search_reader(matcher, s.as_bytes(), sink)
-        })
+    fn search_string(&mut self, s: &str) -> anyhow::Result<Vec<usize>> {
+        self.search_bytes(s.as_bytes())
+    }
+}
+
+/// Searches the given crates in the given files, and returns those used by at least one of them.
+fn search_files(
+    paths: &[PathBuf],
+    crates: BTreeSet<SearchedCrate>,
+    backend: SearchBackend,
+) -> HashSet<SearchedCrate> {
+    if crates.is_empty() {
+        return HashSet::new();
     }
+
+    let crates = Vec::from_iter(crates);
+    let search = Search::new(&crates, backend).expect("constructing grep context");
+
+    let found: HashSet<usize> = paths
+        .par_iter()
+        .map_with(search, |search, path| {
+            trace!("looking for crates in {}", path.to_string_lossy());
+            search.search_path(path).unwrap_or_else(|err| {
+                eprintln!("{}: {}", path.display(), err);
+                Vec::new()
+            })
+        })
+        .flatten_iter()
+        .collect();
+
+    found.into_iter().map(|i| crates[i].clone()).collect()
+}
+
+/// A package manifest, completed with the data from its workspace manifest.
+struct FullManifest {
+    manifest: cargo_toml::Manifest<PackageMetadata>,
+
+    /// Path to the workspace manifest, if the package is part of a workspace.
+    workspace_manifest_path: Option<PathBuf>,
+
+    /// The `workspace.metadata.cargo-machete` custom metadata, if the package is part of a
+    /// workspace.
+    workspace_metadata: Option<MetadataFields>,
+
+    /// Names of the dependencies inherited from the workspace (with `dep.workspace = true`).
+    inherited_dependencies: BTreeSet<String>,
+}
+
+/// Returns the names of the dependencies inherited from the workspace in a non-completed manifest.
+fn inherited_dependencies(manifest: &cargo_toml::Manifest<PackageMetadata>) -> BTreeSet<String> {
+    manifest
+        .dependencies
+        .iter()
+        .chain(&manifest.dev_dependencies)
+        .chain(&manifest.build_dependencies)
+        .chain(manifest.target.values().flat_map(|target| {
+            target
+                .dependencies
+                .iter()
+                .chain(&target.dev_dependencies)
+                .chain(&target.build_dependencies)
+        }))
+        .filter(|(_, dep)| matches!(dep, cargo_toml::Dependency::Inherited(_)))
+        .map(|(name, _)| name.clone())
+        .collect()
 }
 
 /// Read a manifest and try to find a workspace manifest to complete the data available in the
 /// manifest.
 ///
 /// This will look up the file tree to find the Cargo.toml workspace manifest, assuming it's on a
-/// parent directory.
-fn get_full_manifest(
-    dir_path: &Path,
-    manifest_path: &Path,
-) -> anyhow::Result<(
-    cargo_toml::Manifest<PackageMetadata>,
-    Option<meta::MetadataFields>,
-)> {
+/// parent directory, unless the manifest is a workspace manifest itself.
+fn get_full_manifest(dir_path: &Path, manifest_path: &Path) -> anyhow::Result<FullManifest> {
     // HACK: we can't plain use `from_path_with_metadata` here, because it calls
     // `complete_from_path` just a bit too early (before we've had a chance to call
     // `inherit_workspace`). See https://gitlab.com/crates.rs/cargo_toml/-/issues/20 for details,
@@ -280,13 +781,30 @@ fn get_full_manifest(
     let mut manifest =
         cargo_toml::Manifest::<PackageMetadata>::from_slice_with_metadata(&cargo_toml_content)?;
 
+    let inherited_dependencies = inherited_dependencies(&manifest);
+
+    if manifest.workspace.is_some() {
+        // The package is the root of its own workspace: cargo_toml will complete the manifest
+        // from itself.
+        manifest.complete_from_path_and_workspace::<PackageMetadata>(manifest_path, None)?;
+
+        let workspace_metadata = manifest
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.metadata.as_ref()?.cargo_machete.clone());
+
+        return Ok(FullManifest {
+            manifest,
+            workspace_manifest_path: Some(canonicalize_or_warn(manifest_path)),
+            workspace_metadata,
+            inherited_dependencies,
+        });
+    }
+
     let mut ws_manifest_and_path = None;
 
     // Canonicalize the path, so as to get the full "parenthood" of relative paths.
-    let mut dir_path = std::fs::canonicalize(dir_path).unwrap_or_else(|err| {
-        warn!("error when canonicalizing dir_path: {err}");
-        dir_path.to_owned()
-    });
+    let mut dir_path = canonicalize_or_warn(dir_path);
 
     // Try to find a workspace manifest, starting from the current directory, going up to the
     // filesystem's root.
@@ -306,24 +824,70 @@ fn get_full_manifest(
         ws_manifest_and_path.as_ref().map(|(m, p)| (m, p.as_path())),
     )?;
 
-    Ok((
+    let (workspace_manifest_path, workspace_metadata) = match ws_manifest_and_path {
+        Some((ws_manifest, ws_path)) => (
+            Some(ws_path),
+            // Look for `workspace.metadata.cargo-machete` custom metadata in the workspace
+            // Cargo.toml.
+            ws_manifest
+                .workspace
+                .and_then(|ws| ws.metadata?.cargo_machete),
+        ),
+        None => (None, None),
+    };
+
+    Ok(FullManifest {
         manifest,
-        // Look for `workspace.metadata.cargo-machete` custom metadata in the workspace Cargo.toml.
-        ws_manifest_and_path
-            .and_then(|(manifest, _path)| manifest.workspace?.metadata?.cargo_machete),
-    ))
+        workspace_manifest_path,
+        workspace_metadata,
+        inherited_dependencies,
+    })
+}
+
+/// Returns the key of the manifest's `[target.'…']` table for the given platform.
+///
+/// `cargo metadata` formats platforms its own way (e.g. `cfg(any(unix,windows))` becomes
+/// `cfg(any(unix, windows))`), while the key as written is needed to locate and remove the
+/// dependencies of the table.
+fn target_key(
+    manifest: &cargo_toml::Manifest<PackageMetadata>,
+    platform: &cargo_metadata::cargo_platform::Platform,
+) -> String {
+    manifest
+        .target
+        .keys()
+        .find(|key| {
+            key.parse::<cargo_metadata::cargo_platform::Platform>()
+                .is_ok_and(|parsed| parsed == *platform)
+        })
+        .cloned()
+        .unwrap_or_else(|| platform.to_string())
+}
+
+/// Canonicalizes a path, falling back to the original path (with a warning) if that fails.
+fn canonicalize_or_warn(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|err| {
+        warn!("error when canonicalizing {}: {err}", path.display());
+        path.to_owned()
+    })
 }
 
 pub(crate) fn find_unused(
     manifest_path: &Path,
     with_cargo_metadata: UseCargoMetadata,
+    backend: SearchBackend,
 ) -> anyhow::Result<Option<PackageAnalysis>> {
     let mut dir_path = manifest_path.to_path_buf();
     dir_path.pop();
 
     trace!("trying to open {}...", manifest_path.display());
 
-    let (manifest, workspace_metadata) = get_full_manifest(&dir_path, manifest_path)?;
+    let FullManifest {
+        manifest,
+        workspace_manifest_path,
+        workspace_metadata,
+        inherited_dependencies,
+    } = get_full_manifest(&dir_path, manifest_path)?;
 
     let package_name = match manifest.package {
         Some(ref package) => package.name.clone(),
@@ -338,15 +902,17 @@ pub(crate) fn find_unused(
         manifest,
         matches!(with_cargo_metadata, UseCargoMetadata::Yes),
     )?;
+    analysis.workspace_manifest_path = workspace_manifest_path;
+    analysis.inherited_dependencies = inherited_dependencies;
 
     let paths = collect_paths(&dir_path, &analysis);
+    let build_script_paths = collect_build_script_paths(&dir_path, &analysis);
 
-    // TODO extend to dev dependencies + build dependencies, and be smarter in the grouping of
-    // searched paths
-    // Maps dependency name (the name of the key in the Cargo.toml dependency
+    // TODO be smarter in the grouping of searched paths
+    // Maps dependency kind, target and name (the name of the key in the Cargo.toml dependency
     // table, can have dashes, not necessarily the name in the crate registry)
     // to crate name (extern crate, snake case)
-    let dependencies: BTreeMap<String, String> = if let Some((metadata, resolve)) = analysis
+    let dependencies: BTreeMap<_, String> = if let Some((metadata, resolve)) = analysis
         .metadata
         .as_ref()
         .and_then(|metadata| metadata.resolve.as_ref().map(|resolve| (metadata, resolve)))
@@ -365,14 +931,15 @@ pub(crate) fn find_unused(
                 .iter()
                 .find(|pkg| pkg.id == *root)
                 .expect("root should appear under cargo-metadata packages");
-            // For every resolved dependency:
+            // For every resolved dependency, and every kind it's used as:
             // look it up in the package list to find the name (the one in registries)
             // look up that name in dependencies of the root_package;
             // find if it uses a different key through the rename field
+            let manifest = &analysis.manifest;
             root_node
                 .deps
                 .iter()
-                .map(|dep| {
+                .flat_map(|dep| {
                     let crate_name = dep.name.clone();
                     let dep_pkg = metadata
                         .packages
@@ -382,25 +949,40 @@ pub(crate) fn find_unused(
                             "resolved dependencies should appear under cargo-metadata packages",
                         );
 
-                    let mut dep_spec_it = root_package
-                        .dependencies
-                        .iter()
-                        .filter(|dep_spec| dep_spec.name == *dep_pkg.name);
-
-                    // The dependency can appear more than once, for example if it is both
-                    // a dependency and a dev-dependency (often with more features enabled).
-                    // We'll assume cargo enforces consistency.
-                    let dep_spec = dep_spec_it
-                        .next()
-                        .expect("resolved dependency should have a matching dependency spec");
-
-                    // If the dependency was renamed, through key = { package = … },
-                    // the original key is in dep_spec.rename.
-                    let dep_key = dep_spec
-                        .rename
-                        .clone()
-                        .unwrap_or_else(|| dep_spec.name.clone());
-                    (dep_key, crate_name)
+                    dep.dep_kinds.iter().filter_map(move |dep_kind| {
+                        let kind = match dep_kind.kind {
+                            cargo_metadata::DependencyKind::Normal => DependencyKind::Normal,
+                            cargo_metadata::DependencyKind::Development => {
+                                DependencyKind::Development
+                            }
+                            cargo_metadata::DependencyKind::Build => DependencyKind::Build,
+                            cargo_metadata::DependencyKind::Unknown => return None,
+                        };
+
+                        // The dependency can appear more than once for a given kind and target,
+                        // if it's renamed several times. We'll assume cargo enforces consistency.
+                        let dep_spec = root_package
+                            .dependencies
+                            .iter()
+                            .find(|dep_spec| {
+                                dep_spec.name == *dep_pkg.name
+                                    && dep_spec.kind == dep_kind.kind
+                                    && dep_spec.target == dep_kind.target
+                            })
+                            .expect("resolved dependency should have a matching dependency spec");
+
+                        // If the dependency was renamed, through key = { package = … },
+                        // the original key is in dep_spec.rename.
+                        let dep_key = dep_spec
+                            .rename
+                            .clone()
+                            .unwrap_or_else(|| dep_spec.name.clone());
+                        let target = dep_kind
+                            .target
+                            .as_ref()
+                            .map(|platform| target_key(manifest, platform));
+                        Some(((kind, target, dep_key), crate_name.clone()))
+                    })
                 })
                 .collect()
         } else {
@@ -408,11 +990,39 @@ pub(crate) fn find_unused(
             Default::default()
         }
     } else {
-        analysis
-            .manifest
-            .dependencies
-            .keys()
-            .map(|k| (k.clone(), k.replace('-', "_")))
+        let manifest = &analysis.manifest;
+        let deps_sets = [
+            (DependencyKind::Normal, None, &manifest.dependencies),
+            (
+                DependencyKind::Development,
+                None,
+                &manifest.dev_dependencies,
+            ),
+            (DependencyKind::Build, None, &manifest.build_dependencies),
+        ];
+        // Platform-specific dependencies, e.g. `[target.'cfg(unix)'.dependencies]`.
+        let target_deps_sets = manifest.target.iter().flat_map(|(target, deps)| {
+            [
+                (DependencyKind::Normal, Some(target), &deps.dependencies),
+                (
+                    DependencyKind::Development,
+                    Some(target),
+                    &deps.dev_dependencies,
+                ),
+                (
+                    DependencyKind::Build,
+                    Some(target),
+                    &deps.build_dependencies,
+                ),
+            ]
+        });
+        deps_sets
+            .into_iter()
+            .chain(target_deps_sets)
+            .flat_map(|(kind, target, deps)| {
+                deps.keys()
+                    .map(move |k| ((kind, target.cloned(), k.clone()), k.replace('-', "_")))
+            })
             .collect()
     };
 
@@ -435,16 +1045,10 @@ pub(crate) fn find_unused(
         .map(|MetadataFields { ignored, renamed }| (HashSet::from_iter(ignored), renamed))
         .unwrap_or_default();
 
-    enum SingleDepResult {
-        /// Dependency is unused and not marked as ignored.
-        Unused(String),
-        /// Dependency is marked as ignored but used.
-        IgnoredButUsed(String),
-    }
-
-    let results: Vec<SingleDepResult> = dependencies
-        .into_par_iter()
-        .filter_map(|(dep_name, crate_name)| {
+    // Associate each dependency with the crate to look for in the source files.
+    let dependencies: Vec<_> = dependencies
+        .into_iter()
+        .map(|((kind, target, dep_name), crate_name)| {
             // If the crate was renamed (in the current Cargo.toml), use the renamed name.
             let crate_name = renamed
                 .get(dep_name.as_str())
@@ -453,49 +1057,229 @@ pub(crate) fn find_unused(
                 // fall-back to the crate name
                 .map_or(crate_name.as_str(), Box::as_ref);
 
-            let mut search = Search::new(crate_name).expect("constructing grep context");
-
-            let mut found_once = false;
-            for path in &paths {
-                trace!("looking for {} in {}", crate_name, path.to_string_lossy(),);
-                match search.search_path(path) {
-                    Ok(true) => {
-                        found_once = true;
-                        break;
-                    }
-                    Ok(false) => {}
-                    Err(err) => {
-                        eprintln!("{}: {}", path.display(), err);
-                    }
-                };
-            }
+            // Dev-dependencies are searched in the same files as normal dependencies, since
+            // those include the tests, benches and examples, as well as `#[cfg(test)]` modules;
+            // they may also be used in doc-tests, so look into doc comments too.
+            let searched = SearchedCrate {
+                name: crate_name.to_owned(),
+                match_doc_comments: kind == DependencyKind::Development,
+            };
 
-            if !found_once {
-                if ignored.contains(&dep_name) || workspace_ignored.contains(&dep_name) {
-                    return None;
-                }
+            (kind, target, dep_name, searched)
+        })
+        .collect();
 
-                Some(SingleDepResult::Unused(dep_name))
-            } else {
-                if ignored.contains(&dep_name) {
-                    return Some(SingleDepResult::IgnoredButUsed(dep_name));
-                }
+    // Build dependencies can only be used by the build script, so they're searched separately.
+    let searched_crates = |build: bool| {
+        dependencies
+            .iter()
+            .filter(|(kind, ..)| (*kind == DependencyKind::Build) == build)
+            .map(|(.., searched)| searched.clone())
+            .collect()
+    };
+    let used_in_sources = search_files(&paths, searched_crates(false), backend);
+    let used_in_build_script = search_files(&build_script_paths, searched_crates(true), backend);
+
+    // Read the manifest again, with the positions of its items, to locate the findings.
+    let locator = ManifestLocator::read(manifest_path)
+        .inspect_err(|err| {
+            warn!(
+                "couldn't locate dependencies in {}: {err}",
+                manifest_path.display()
+            )
+        })
+        .ok();
 
-                None
+    for (kind, target, dep_name, searched) in dependencies {
+        let used = match kind {
+            DependencyKind::Build => used_in_build_script.contains(&searched),
+            DependencyKind::Normal | DependencyKind::Development => {
+                used_in_sources.contains(&searched)
             }
-        })
-        .collect();
+        };
 
-    for result in results {
-        match result {
-            SingleDepResult::Unused(dep) => analysis.unused.push(dep),
-            SingleDepResult::IgnoredButUsed(dep) => analysis.ignored_used.push(dep),
+        if !used {
+            if !ignored.contains(&dep_name) && workspace_ignored.contains(&dep_name) {
+                analysis.ignored_by_workspace.insert(dep_name);
+            } else if !ignored.contains(&dep_name) {
+                let location = locator
+                    .as_ref()
+                    .and_then(|locator| locator.dependency(kind, target.as_deref(), &dep_name));
+                analysis.unused.push(UnusedDependency {
+                    name: dep_name,
+                    kind,
+                    target,
+                    location,
+                });
+            }
+        } else if ignored.contains(&dep_name) {
+            // The same name may be used as several kinds of dependencies; only report it once.
+            if !analysis.ignored_used.iter().any(|dep| dep.name == dep_name) {
+                let location = locator
+                    .as_ref()
+                    .and_then(|locator| locator.any_dependency(&dep_name));
+                analysis.ignored_used.push(DependencyKey {
+                    name: dep_name,
+                    location,
+                });
+            }
         }
     }
 
     Ok(Some(analysis))
 }
 
+/// Returns the canonical paths to the manifests of a workspace's members: the root package, if
+/// any, and the packages matched by the `members` globs and not by the `exclude` list.
+fn workspace_members(
+    manifest_path: &Path,
+    has_root_package: bool,
+    workspace: &cargo_toml::Workspace<PackageMetadata>,
+) -> anyhow::Result<BTreeSet<PathBuf>> {
+    let root = canonicalize_or_warn(manifest_path.parent().unwrap_or(Path::new(".")));
+    let excluded = workspace
+        .exclude
+        .iter()
+        .map(|path| std::fs::canonicalize(root.join(path)).unwrap_or_else(|_| root.join(path)))
+        .collect::<Vec<_>>();
+
+    let mut members = BTreeSet::new();
+    if has_root_package {
+        members.insert(canonicalize_or_warn(manifest_path));
+    }
+    for pattern in &workspace.members {
+        let pattern = root.join(pattern);
+        let paths = glob::glob(&pattern.to_string_lossy()).map_err(|err| {
+            anyhow::anyhow!("invalid workspace member {}: {err}", pattern.display())
+        })?;
+        for path in paths {
+            let member = canonicalize_or_warn(&path?).join("Cargo.toml");
+            if member.is_file() && !excluded.iter().any(|path| member.starts_with(path)) {
+                members.insert(member);
+            }
+        }
+    }
+    Ok(members)
+}
+
+/// Looks for the dependencies declared in the `[workspace.dependencies]` table of the given
+/// manifest, which aren't inherited by any of the given packages.
+///
+/// The packages must include all the members of the workspace, otherwise dependencies only
+/// inherited by the missing members would be reported as unused.
+///
+/// The entries of the workspace's `ignored` list which hide no unused dependency are only looked
+/// for if `check_ignored` is set.
+///
+/// Returns `Ok(None)` if the manifest isn't a workspace manifest.
+pub(crate) fn find_unused_workspace_dependencies<'a>(
+    manifest_path: &Path,
+    packages: impl IntoIterator<Item = &'a PackageAnalysis>,
+    check_ignored: bool,
+) -> anyhow::Result<Option<WorkspaceAnalysis>> {
+    // No need to complete the manifest, since only the `workspace` table is looked at.
+    let manifest = cargo_toml::Manifest::<PackageMetadata>::from_slice_with_metadata(
+        &std::fs::read(manifest_path)?,
+    )?;
+
+    let Some(workspace) = manifest.workspace else {
+        return Ok(None);
+    };
+
+    debug!("handling workspace {}", manifest_path.display());
+
+    let canonical_path = canonicalize_or_warn(manifest_path);
+
+    let members = packages
+        .into_iter()
+        .filter(|package| package.workspace_manifest_path.as_ref() == Some(&canonical_path))
+        .collect::<Vec<_>>();
+
+    // A workspace dependency only inherited by a member which couldn't be analyzed would be
+    // reported as unused, so the workspace is only analyzed along with all its members.
+    let analyzed = members
+        .iter()
+        .map(|package| canonicalize_or_warn(&package.manifest_path))
+        .collect::<BTreeSet<_>>();
+    let missing = workspace_members(manifest_path, manifest.package.is_some(), &workspace)?
+        .into_iter()
+        .filter(|member| !analyzed.contains(member))
+        .map(|member| {
+            let root = canonical_path.parent().unwrap_or(Path::new(""));
+            member
+                .strip_prefix(root)
+                .unwrap_or(&member)
+                .display()
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "the workspace dependencies can't be checked without analyzing all the workspace \
+             members, missing: {}",
+            missing.join(", ")
+        );
+    }
+
+    let inherited = members
+        .iter()
+        .flat_map(|package| &package.inherited_dependencies)
+        .collect::<HashSet<_>>();
+
+    let ignored = workspace
+        .metadata
+        .and_then(|metadata| metadata.cargo_machete)
+        .map(|meta| meta.ignored)
+        .unwrap_or_default();
+
+    let locator = ManifestLocator::read(manifest_path)
+        .inspect_err(|err| {
+            warn!(
+                "couldn't locate dependencies in {}: {err}",
+                manifest_path.display()
+            )
+        })
+        .ok();
+    let key = |name: String| DependencyKey {
+        location: locator
+            .as_ref()
+            .and_then(|locator| locator.workspace_dependency(&name)),
+        name,
+    };
+
+    let (unused, ignored_unused): (Vec<_>, Vec<_>) = workspace
+        .dependencies
+        .into_keys()
+        .filter(|name| !inherited.contains(name))
+        .partition(|name| !ignored.contains(name));
+
+    // An ignored dependency is still needed if it hides an unused dependency, either of a member
+    // or of the workspace itself.
+    let ignored_used = ignored
+        .into_iter()
+        .filter(|name| {
+            check_ignored
+                && !ignored_unused.contains(name)
+                && !members
+                    .iter()
+                    .any(|package| package.ignored_by_workspace.contains(name))
+        })
+        .map(|name| DependencyKey {
+            location: locator
+                .as_ref()
+                .and_then(|locator| locator.workspace_ignored(&name)),
+            name,
+        })
+        .collect();
+
+    Ok(Some(WorkspaceAnalysis {
+        manifest_path: manifest_path.to_owned(),
+        unused: unused.into_iter().map(key).collect(),
+        ignored_used,
+    }))
+}
+
+#[derive(Clone)]
 struct StopAfterFirstMatch {
     found: bool,
 }
@@ -512,20 +1296,10 @@ impl Sink for StopAfterFirstMatch {
     fn matched(
         &mut self,
         _searcher: &searcher::Searcher,
-        matsh: &searcher::SinkMatch<'_>,
+        _mat: &searcher::SinkMatch<'_>,
     ) -> Result<bool, Self::Error> {
-        let mat = std::str::from_utf8(matsh.bytes())?;
-        let mat = mat.trim();
-
-        if mat.starts_with("//") || mat.starts_with("//!") {
-            // Continue if seeing what resembles a comment or doc comment. Unfortunately we can't
-            // do anything better because trying to figure whether we're within a (doc) comment
-            // would require actual parsing of the Rust code.
-            return Ok(true);
-        }
-
-        // Otherwise, we've found it: mark to true, and return false to indicate that we can stop
-        // searching.
+        // Comments and string literals have been blanked out beforehand, so any match is a use of
+        // the crate: mark to true, and return false to indicate that we can stop searching.
         self.found = true;
         Ok(false)
     }
@@ -533,10 +1307,22 @@ impl Sink for StopAfterFirstMatch {
 
 #[test]
 fn test_regexp() -> anyhow::Result<()> {
-    fn test_one(crate_name: &str, content: &str) -> anyhow::Result<bool> {
-        let mut search = Search::new(crate_name)?;
-        search.search_string(content)
+    for backend in SearchBackend::all() {
+        test_search_corpus(*backend)?;
     }
+    Ok(())
+}
+
+#[cfg(test)]
+fn test_search_corpus(backend: SearchBackend) -> anyhow::Result<()> {
+    let test_one = |crate_name: &str, content: &str| -> anyhow::Result<bool> {
+        let searched = SearchedCrate {
+            name: crate_name.to_owned(),
+            match_doc_comments: false,
+        };
+        let mut search = Search::new(&[searched], backend)?;
+        Ok(!search.search_string(content)?.is_empty())
+    };
 
     assert!(!test_one("log", "use da_force_luke;")?);
     assert!(!test_one("log", "use flog;")?);
@@ -764,27 +1550,274 @@ pub use {
     // Detects top level usage
     assert!(test_one("futures", r#" ::futures::mod1"#)?);
 
+    // No false usage detection in block comments and string literals
+    assert!(!test_one("serde", "/* serde::Serialize */ struct S;")?);
+    assert!(!test_one(
+        "serde",
+        "/* outer /* nested */ serde::Serialize */ struct S;"
+    )?);
+    assert!(!test_one(
+        "serde",
+        r#"
+/**
+ * Don't use serde::Serialize here.
+ */
+struct S;
+"#
+    )?);
+    assert!(!test_one("log", r#"const S: &str = "log::info!";"#)?);
+    assert!(!test_one(
+        "log",
+        r#"const S: &str = "\"quoted\" log::info!";"#
+    )?);
+    assert!(!test_one(
+        "log",
+        r##"const S: &str = r#"log::info!("fyi")"#;"##
+    )?);
+    assert!(!test_one("log", r#"const S: &[u8] = b"log::info!";"#)?);
+
+    // ... except in attributes, where strings may name crate paths
+    assert!(test_one(
+        "humantime_serde",
+        r#"
+#[derive(serde::Deserialize)]
+struct S {
+    #[serde(deserialize_with = "humantime_serde::deserialize")]
+    timeout: std::time::Duration,
+}
+"#
+    )?);
+    assert!(test_one(
+        "humantime_serde",
+        r#"
+#[derive(serde::Deserialize)]
+struct S {
+    #[serde(with = "humantime_serde")]
+    timeout: std::time::Duration,
+}
+"#
+    )?);
+    assert!(test_one(
+        "serde",
+        r#"#[derive(serde_derive::Serialize)] #[serde(crate = "serde")] struct S;"#
+    )?);
+    assert!(!test_one(
+        "humantime_serde",
+        r#"#[serde(rename = "humantime_serde")] struct S;"#
+    )?);
+
+    // ... but matches right after them are still detected
+    assert!(test_one("log", r#"/* comment */ log::info!("fyi");"#)?);
+    assert!(test_one(
+        "log",
+        r##"let s = r#"a "quoted" string"#; log::info!("{s}");"##
+    )?);
+    assert!(test_one("log", r#"let c = '"'; log::info!("{c}");"#)?);
+    assert!(test_one(
+        "log",
+        r#"fn f<'a>(s: &'a str) { log::info!("{s}"); }"#
+    )?);
+
+    Ok(())
+}
+
+#[test]
+fn test_lexer() {
+    let blank = |code: &str, keep_doc_comments: bool| {
+        String::from_utf8(lexer::blank_comments_and_strings(
+            code.as_bytes(),
+            keep_doc_comments,
+        ))
+        .unwrap()
+    };
+
+    // Replaces the given literal or comment with spaces, between `a` and `c`.
+    let blanked = |literal: &str| format!("a {} c", " ".repeat(literal.len()));
+
+    assert_eq!(blank("a // b\nc", false), "a     \nc");
+    assert_eq!(blank("a /* b\n */ c", false), "a     \n    c");
+    assert_eq!(blank("a /* /* b */ */ c", false), blanked("/* /* b */ */"));
+    assert_eq!(blank(r#"a "b\"c" c"#, false), blanked(r#""b\"c""#));
+    assert_eq!(blank(r##"a r#"b"c"# c"##, false), blanked(r##"r#"b"c"#"##));
+    assert_eq!(blank(r#"a br"b" c"#, false), blanked(r#"br"b""#));
+    assert_eq!(blank("a '\\'' c", false), blanked("'\\''"));
+    assert_eq!(blank("a 'é' c", false), blanked("'é'"));
+    assert_eq!(blank("r#type 'a: loop", false), "r#type 'a: loop");
+
+    // Strings in attributes are kept, but not the comments.
+    assert_eq!(blank(r#"#[a(b = "c")] "d""#, false), r#"#[a(b = "c")]    "#);
+    assert_eq!(
+        blank(r#"#![a(b = "]", c = "d" /* e */)] "f""#, false),
+        r#"#![a(b = "]", c = "d"        )]    "#
+    );
+
+    // Modules named in attributes are found in the blanked code.
+    let modules = |code: &str| {
+        let mut names =
+            Vec::from_iter(lexer::attribute_module_names(blank(code, false).as_bytes()));
+        names.sort();
+        names
+    };
+    assert_eq!(
+        modules(r#"#[serde(with = "a", deserialize_with = "B::f", crate="c", rename = "d")] "e""#),
+        &["a", "c"]
+    );
+    assert_eq!(modules(r#"let with = "a";"#), Vec::<String>::new());
+
+    // Doc comments are kept only if asked to.
+    assert_eq!(blank("/// a\n//! b\n// c", true), "/// a\n//! b\n    ");
+    assert_eq!(
+        blank("/** a */ /*! b */ /* c */", true),
+        "/** a */ /*! b */        "
+    );
+    assert_eq!(blank("/// a", false), "     ");
+}
+
+#[test]
+fn test_syntax_search() -> anyhow::Result<()> {
+    fn test_one(crate_name: &str, content: &str, match_doc_comments: bool) -> anyhow::Result<bool> {
+        let searched = SearchedCrate {
+            name: crate_name.to_owned(),
+            match_doc_comments,
+        };
+        let mut search = Search::new(&[searched], SearchBackend::Syntax)?;
+        Ok(!search.search_string(content)?.is_empty())
+    }
+
+    // `use` trees of any depth.
+    assert!(test_one(
+        "futures",
+        r#"use { a::{b::{c::{d::{e::{f}}}}}, futures::stream };"#,
+        false
+    )?);
+    assert!(!test_one(
+        "futures",
+        r#"use { a::{b::{c::{d::{e::{futures}}}}} };"#,
+        false
+    )?);
+
+    // Paths in attributes and macro invocations.
+    assert!(test_one(
+        "serde",
+        "#[derive(serde::Serialize)] struct S;",
+        false
+    )?);
+    assert!(test_one(
+        "tokio",
+        "#[tokio::main] async fn main() {}",
+        false
+    )?);
+    assert!(test_one(
+        "log",
+        r#"fn main() { println!("{:?}", log::max_level()); }"#,
+        false
+    )?);
+
+    // Doc-tests, only when looking into doc comments.
+    let doc_test = r#"
+/// ```
+/// # use log::info;
+/// info!("fyi");
+/// ```
+fn main() {}
+"#;
+    assert!(test_one("log", doc_test, true)?);
+    assert!(!test_one("log", doc_test, false)?);
+
+    // Code blocks in other languages aren't doc-tests.
+    assert!(!test_one(
+        "log",
+        r#"
+/// ```text
+/// log::info!("fyi");
+/// ```
+fn main() {}
+"#,
+        true
+    )?);
+
+    Ok(())
+}
+
+#[test]
+fn test_multi_search() -> anyhow::Result<()> {
+    let searched = |name: &str, match_doc_comments| SearchedCrate {
+        name: name.to_owned(),
+        match_doc_comments,
+    };
+    let crates = [
+        searched("log", false),
+        searched("log", true),
+        searched("log_once", false),
+        searched("rand", false),
+        searched("rand", true),
+        searched("serde", false),
+        searched("Futures", false),
+    ];
+
+    let content = r#"
+use log_once::info_once;
+use futures::future;
+
+/// ```
+/// let x = rand::random::<u8>();
+/// ```
+fn main() {
+    // log::info!("commented out");
+    info_once!("fyi");
+    let s = "serde::Serialize";
+}
+"#;
+
+    for backend in SearchBackend::all() {
+        let mut search = Search::new(&crates, *backend)?;
+        let found = search.search_string(content)?;
+        assert_eq!(found, vec![2, 4, 6]);
+
+        // The same search can be reused for other files, where crates that have already been found
+        // aren't looked for anymore.
+        assert_eq!(search.search_string("use serde;")?, vec![5]);
+        assert_eq!(search.search_string("fn main() {}")?, Vec::<usize>::new());
+        assert_eq!(
+            search.clone().search_string("use log_once; use log;")?,
+            vec![0, 1]
+        );
+    }
+
     Ok(())
 }
 
 #[cfg(test)]
 fn check_analysis<F: Fn(PackageAnalysis)>(rel_path: &str, callback: F) {
     for use_cargo_metadata in UseCargoMetadata::all() {
-        let analysis = find_unused(
-            &PathBuf::from(TOP_LEVEL).join(rel_path),
-            *use_cargo_metadata,
-        )
-        .expect("find_unused must return an Ok result")
-        .expect("no error during processing");
-        callback(analysis);
+        for backend in SearchBackend::all() {
+            let analysis = find_unused(
+                &PathBuf::from(TOP_LEVEL).join(rel_path),
+                *use_cargo_metadata,
+                *backend,
+            )
+            .expect("find_unused must return an Ok result")
+            .expect("no error during processing");
+            callback(analysis);
+        }
     }
 }
 
+/// The location of a dependency declared at the start of the given line, in the given table.
+#[cfg(test)]
+fn at(table: &str, line: usize) -> Option<Location> {
+    Some(Location {
+        table: table.to_owned(),
+        line,
+        column: 1,
+    })
+}
+
 #[test]
 fn test_just_unused() {
     // a crate that simply does not use a dependency it refers to
     check_analysis("./integration-tests/just-unused/Cargo.toml", |analysis| {
-        assert_eq!(analysis.unused, &["log".to_string()]);
+        assert_eq!(analysis.unused_names(), &["log"]);
     });
 }
 
@@ -794,7 +1827,7 @@ fn test_just_unused_with_manifest() {
     check_analysis(
         "./integration-tests/workspace-package/program/Cargo.toml",
         |analysis| {
-            assert_eq!(analysis.unused, &["log".to_string()]);
+            assert_eq!(analysis.unused_names(), &["log"]);
         },
     );
 }
@@ -821,7 +1854,7 @@ fn test_unused_transitive() {
     check_analysis(
         "./integration-tests/unused-transitive/Cargo.toml",
         |analysis| {
-            assert_eq!(analysis.unused, &["lib1".to_string()]);
+            assert_eq!(analysis.unused_names(), &["lib1"]);
         },
     );
 }
@@ -833,7 +1866,7 @@ fn test_false_positive_macro_use() {
     check_analysis(
         "./integration-tests/false-positive-log/Cargo.toml",
         |analysis| {
-            assert_eq!(analysis.unused, &["log".to_string()]);
+            assert_eq!(analysis.unused_names(), &["log"]);
         },
     );
 }
@@ -857,9 +1890,10 @@ fn test_renamed_field_works() -> anyhow::Result<()> {
     let analysis = find_unused(
         &PathBuf::from(TOP_LEVEL).join("./integration-tests/renamed-dep/Cargo.toml"),
         UseCargoMetadata::No,
+        SearchBackend::Regex,
     )?
     .expect("no error during processing");
-    assert_eq!(analysis.unused.as_slice(), &["bytes", "log"]);
+    assert_eq!(analysis.unused_names(), &["bytes", "log"]);
     Ok(())
 }
 
@@ -871,9 +1905,10 @@ fn test_renamed_field_workspace_works() -> anyhow::Result<()> {
         &PathBuf::from(TOP_LEVEL)
             .join("./integration-tests/renamed-dep-workspace/inner/Cargo.toml"),
         UseCargoMetadata::No,
+        SearchBackend::Regex,
     )?
     .expect("no error during processing");
-    assert_eq!(analysis.unused.as_slice(), &["bytes", "flagset"]);
+    assert_eq!(analysis.unused_names(), &["bytes", "flagset"]);
     Ok(())
 }
 
@@ -884,6 +1919,7 @@ fn test_crate_renaming_works() -> anyhow::Result<()> {
     let analysis = find_unused(
         &PathBuf::from(TOP_LEVEL).join("./integration-tests/renaming-works/Cargo.toml"),
         UseCargoMetadata::Yes,
+        SearchBackend::Regex,
     )?
     .expect("no error during processing");
     assert!(analysis.unused.is_empty());
@@ -892,9 +1928,10 @@ fn test_crate_renaming_works() -> anyhow::Result<()> {
     let analysis = find_unused(
         &PathBuf::from(TOP_LEVEL).join("./integration-tests/renaming-works/Cargo.toml"),
         UseCargoMetadata::No,
+        SearchBackend::Regex,
     )?
     .expect("no error during processing");
-    assert_eq!(analysis.unused, &["xml-rs".to_string()]);
+    assert_eq!(analysis.unused_names(), &["xml-rs"]);
 
     Ok(())
 }
@@ -906,9 +1943,10 @@ fn test_unused_renamed_in_registry() -> anyhow::Result<()> {
     let analysis = find_unused(
         &PathBuf::from(TOP_LEVEL).join("./integration-tests/unused-renamed-in-registry/Cargo.toml"),
         UseCargoMetadata::Yes,
+        SearchBackend::Regex,
     )?
     .expect("no error during processing");
-    assert_eq!(analysis.unused, &["xml-rs".to_string()]);
+    assert_eq!(analysis.unused_names(), &["xml-rs"]);
 
     Ok(())
 }
@@ -920,9 +1958,10 @@ fn test_unused_renamed_in_spec() -> anyhow::Result<()> {
     let analysis = find_unused(
         &PathBuf::from(TOP_LEVEL).join("./integration-tests/unused-renamed-in-spec/Cargo.toml"),
         UseCargoMetadata::Yes,
+        SearchBackend::Regex,
     )?
     .expect("no error during processing");
-    assert_eq!(analysis.unused, &["tracing".to_string()]);
+    assert_eq!(analysis.unused_names(), &["tracing"]);
 
     Ok(())
 }
@@ -933,9 +1972,10 @@ fn test_unused_kebab_spec() -> anyhow::Result<()> {
     let analysis = find_unused(
         &PathBuf::from(TOP_LEVEL).join("./integration-tests/unused-kebab-spec/Cargo.toml"),
         UseCargoMetadata::Yes,
+        SearchBackend::Regex,
     )?
     .expect("no error during processing");
-    assert_eq!(analysis.unused, &["log-once".to_string()]);
+    assert_eq!(analysis.unused_names(), &["log-once"]);
 
     Ok(())
 }
@@ -945,8 +1985,14 @@ fn test_ignore_deps_works() {
     // ensure that ignored deps listed in Cargo.toml package.metadata.cargo-machete.ignored are
     // correctly ignored.
     check_analysis("./integration-tests/ignored-dep/Cargo.toml", |analysis| {
-        assert_eq!(analysis.unused, &["rand".to_string()]);
-        assert_eq!(analysis.ignored_used, &["rand_core".to_string()]);
+        assert_eq!(analysis.unused_names(), &["rand"]);
+        assert_eq!(
+            analysis.ignored_used,
+            &[DependencyKey {
+                name: "rand_core".to_string(),
+                location: at("dependencies", 12),
+            }]
+        );
     });
 }
 
@@ -957,8 +2003,9 @@ fn test_ignore_deps_workspace_works() {
     check_analysis(
         "./integration-tests/ignored-dep-workspace/inner/Cargo.toml",
         |analysis| {
-            assert_eq!(analysis.unused, &["rand".to_string()]);
-            assert_eq!(analysis.ignored_used, &["rand_core".to_string()]);
+            assert_eq!(analysis.unused_names(), &["rand"]);
+            assert_eq!(analysis.ignored_used.len(), 1);
+            assert_eq!(analysis.ignored_used[0].name, "rand_core");
         },
     );
 }
@@ -976,7 +2023,7 @@ fn test_workspace_from_relative_path() {
     .unwrap();
 
     let path = Path::new("./Cargo.toml");
-    let analysis = find_unused(path, UseCargoMetadata::No);
+    let analysis = find_unused(path, UseCargoMetadata::No, SearchBackend::Regex);
 
     // Reset the current directory *before* running any other check.
     set_current_dir(prev_cwd).unwrap();
@@ -985,7 +2032,7 @@ fn test_workspace_from_relative_path() {
         .expect("find_unused must return an Ok result")
         .expect("no error during processing");
 
-    assert_eq!(analysis.unused, &["log".to_string()]);
+    assert_eq!(analysis.unused_names(), &["log"]);
     assert!(analysis.ignored_used.is_empty());
 }
 
@@ -994,9 +2041,243 @@ fn test_multi_key_dep() {
     let analysis = find_unused(
         &PathBuf::from(TOP_LEVEL).join("./integration-tests/multi-key-dep/Cargo.toml"),
         UseCargoMetadata::Yes,
+        SearchBackend::Regex,
     )
     .expect("find_unused must return an Ok result")
     .expect("no error during processing");
 
-    assert_eq!(analysis.unused, &["cc".to_string(), "rand".to_string()]);
+    assert_eq!(analysis.unused_names(), &["cc", "rand"]);
+}
+
+#[test]
+fn test_dev_dependencies() {
+    // dev-dependencies are looked for in tests, examples, `#[cfg(test)]` modules and doc-tests;
+    // mentions in regular comments don't count.
+    check_analysis(
+        "./integration-tests/dev-dependencies/Cargo.toml",
+        |analysis| {
+            assert_eq!(
+                analysis.unused,
+                &[
+                    UnusedDependency {
+                        name: "rand_core".to_string(),
+                        kind: DependencyKind::Development,
+                        target: None,
+                        location: at("dev-dependencies", 14),
+                    },
+                    UnusedDependency {
+                        name: "serde".to_string(),
+                        kind: DependencyKind::Development,
+                        target: None,
+                        location: at("dev-dependencies", 15),
+                    },
+                ]
+            );
+        },
+    );
+}
+
+#[test]
+fn test_build_script_parsing() {
+    assert_eq!(parse_mod_declaration("mod codegen;"), Some("codegen"));
+    assert_eq!(parse_mod_declaration("pub mod codegen;"), Some("codegen"));
+    assert_eq!(
+        parse_mod_declaration("pub(crate) mod codegen;"),
+        Some("codegen")
+    );
+    assert_eq!(parse_mod_declaration("mod codegen {"), None);
+    assert_eq!(parse_mod_declaration("// mod codegen;"), None);
+
+    assert_eq!(
+        parse_path_attribute(r#"#[path = "gen/mod.rs"]"#),
+        Some("gen/mod.rs")
+    );
+    assert_eq!(parse_path_attribute(r#"#[cfg(unix)]"#), None);
+
+    assert_eq!(
+        parse_include(r#"include!("consts.rs");"#),
+        Some("consts.rs")
+    );
+    assert_eq!(
+        parse_include(r#"include!(concat!(env!("OUT_DIR"), "/gen.rs"));"#),
+        None
+    );
+}
+
+#[test]
+fn test_build_dependencies() {
+    // build-dependencies are only looked for in the build script and the files it includes, even
+    // if they're used in the crate's sources. `log` is only used in `build/version.rs`, which
+    // `build/codegen.rs` loads with a `#[path]` attribute relative to its own directory.
+    check_analysis(
+        "./integration-tests/build-dependencies/Cargo.toml",
+        |analysis| {
+            assert_eq!(
+                analysis.unused,
+                &[UnusedDependency {
+                    name: "rand".to_string(),
+                    kind: DependencyKind::Build,
+                    target: None,
+                    location: at("build-dependencies", 14),
+                }]
+            );
+        },
+    );
+}
+
+#[test]
+fn test_target_dependencies() {
+    // platform-specific dependencies are analyzed, whether the target is a `cfg()` expression or
+    // a plain target triple. Targets are reported as written in the manifest, even when
+    // `cargo metadata` formats them differently (`cfg(any(unix, windows))`).
+    let unused = |name: &str, kind: DependencyKind, target: &str, line| UnusedDependency {
+        name: name.to_string(),
+        kind,
+        target: Some(target.to_string()),
+        location: at(&kind.full_table_name(Some(target)), line),
+    };
+
+    check_analysis(
+        "./integration-tests/target-dependencies/Cargo.toml",
+        |analysis| {
+            assert_eq!(
+                analysis.unused,
+                &[
+                    unused(
+                        "lazy_static",
+                        DependencyKind::Normal,
+                        "cfg(any(unix,windows))",
+                        20
+                    ),
+                    unused("rand", DependencyKind::Normal, "cfg(unix)", 8),
+                    unused(
+                        "lazy_static",
+                        DependencyKind::Normal,
+                        "x86_64-pc-windows-msvc",
+                        11
+                    ),
+                    unused("serde", DependencyKind::Build, "cfg(unix)", 17),
+                ]
+            );
+            assert_eq!(
+                analysis
+                    .unused
+                    .iter()
+                    .map(UnusedDependency::table_name)
+                    .collect::<Vec<_>>(),
+                &[
+                    "target.'cfg(any(unix,windows))'.dependencies",
+                    "target.'cfg(unix)'.dependencies",
+                    "target.x86_64-pc-windows-msvc.dependencies",
+                    "target.'cfg(unix)'.build-dependencies",
+                ]
+            );
+        },
+    );
+}
+
+#[test]
+fn test_unused_workspace_dependencies() -> anyhow::Result<()> {
+    // workspace dependencies no member inherits are reported, unless they're ignored in the
+    // workspace metadata.
+    let workspace_path =
+        PathBuf::from(TOP_LEVEL).join("./integration-tests/unused-workspace-dependencies");
+
+    let member = find_unused(
+        &workspace_path.join("member/Cargo.toml"),
+        UseCargoMetadata::No,
+        SearchBackend::Regex,
+    )?
+    .expect("no error during processing");
+    assert!(member.unused.is_empty());
+
+    let analysis =
+        find_unused_workspace_dependencies(&workspace_path.join("Cargo.toml"), [&member], false)?
+            .expect("a workspace manifest");
+    assert_eq!(
+        analysis.unused,
+        &[
+            DependencyKey {
+                name: "rand".to_string(),
+                location: at("workspace.dependencies", 9),
+            },
+            DependencyKey {
+                name: "serde".to_string(),
+                location: at("workspace.dependencies", 10),
+            },
+        ]
+    );
+
+    // Without all its members, the workspace dependencies they inherit aren't known.
+    let err = find_unused_workspace_dependencies(&workspace_path.join("Cargo.toml"), [], false)
+        .err()
+        .expect("a missing member");
+    assert!(err.to_string().contains("member"), "{err}");
+
+    // A package manifest isn't a workspace manifest.
+    assert!(
+        find_unused_workspace_dependencies(
+            &workspace_path.join("member/Cargo.toml"),
+            [&member],
+            false
+        )?
+        .is_none()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_workspace_ignored_used() -> anyhow::Result<()> {
+    // entries of the workspace's ignored list which hide neither an unused dependency of a member
+    // nor an unused workspace dependency are reported.
+    let workspace_path = PathBuf::from(TOP_LEVEL).join("./integration-tests/ignored-dep-workspace");
+
+    let inner = find_unused(
+        &workspace_path.join("inner/Cargo.toml"),
+        UseCargoMetadata::No,
+        SearchBackend::Regex,
+    )?
+    .expect("no error during processing");
+    assert_eq!(
+        inner.ignored_by_workspace.iter().collect::<Vec<_>>(),
+        &["lazy_static", "serde"]
+    );
+
+    let analysis =
+        find_unused_workspace_dependencies(&workspace_path.join("Cargo.toml"), [&inner], true)?
+            .expect("a workspace manifest");
+    assert_eq!(
+        analysis.ignored_used,
+        &[DependencyKey {
+            name: "futures".to_string(),
+            location: Some(Location {
+                table: "workspace.metadata.cargo-machete".to_owned(),
+                line: 12,
+                column: 5,
+            }),
+        }]
+    );
+
+    // They're only looked for if asked to.
+    let analysis =
+        find_unused_workspace_dependencies(&workspace_path.join("Cargo.toml"), [&inner], false)?
+            .expect("a workspace manifest");
+    assert!(analysis.ignored_used.is_empty());
+
+    // `lazy_static` is needed to ignore an unused workspace dependency.
+    let workspace_path =
+        PathBuf::from(TOP_LEVEL).join("./integration-tests/unused-workspace-dependencies");
+    let member = find_unused(
+        &workspace_path.join("member/Cargo.toml"),
+        UseCargoMetadata::No,
+        SearchBackend::Regex,
+    )?
+    .expect("no error during processing");
+    let analysis =
+        find_unused_workspace_dependencies(&workspace_path.join("Cargo.toml"), [&member], true)?
+            .expect("a workspace manifest");
+    assert!(analysis.ignored_used.is_empty());
+
+    Ok(())
 }
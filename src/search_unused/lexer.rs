@@ -0,0 +1,234 @@
+//! A lightweight lexer for Rust source code, which finds comments and string literals so that
+//! the regular expressions don't match in there.
+//!
+//! This doesn't require the code to be valid Rust, unlike a full parser.
+
+use std::collections::HashSet;
+
+/// Returns a copy of the given Rust source code where comments, string literals (including raw,
+/// byte and C strings) and character literals have been replaced with spaces. Line feeds are
+/// preserved, so that the lines of the original code are kept.
+///
+/// String literals within attributes are kept, since they may name crate paths, e.g.
+/// `#[serde(deserialize_with = "humantime_serde::deserialize")]`.
+///
+/// If `keep_doc_comments` is set, doc comments (`///`, `//!`, `/** */` and `/*! */`) are left
+/// untouched.
+pub(super) fn blank_comments_and_strings(code: &[u8], keep_doc_comments: bool) -> Vec<u8> {
+    let mut blanked = code.to_vec();
+    let mut i = 0;
+    // Depth of square brackets within the current attribute, or 0 outside of attributes.
+    let mut attribute_depth = 0usize;
+
+    while i < code.len() {
+        let rest = &code[i..];
+
+        let (len, blank) = if rest.starts_with(b"//") {
+            let len = rest.iter().position(|&c| c == b'\n').unwrap_or(rest.len());
+            let is_doc = (rest.starts_with(b"///") && !rest.starts_with(b"////"))
+                || rest.starts_with(b"//!");
+            (len, !(keep_doc_comments && is_doc))
+        } else if rest.starts_with(b"/*") {
+            let is_doc = (rest.starts_with(b"/**")
+                && !rest.starts_with(b"/***")
+                && !rest.starts_with(b"/**/"))
+                || rest.starts_with(b"/*!");
+            (block_comment_len(rest), !(keep_doc_comments && is_doc))
+        } else if let Some(len) = string_literal_len(rest) {
+            (len, attribute_depth == 0)
+        } else if attribute_depth == 0
+            && let Some(len) = attribute_start_len(rest)
+        {
+            attribute_depth = 1;
+            (len, false)
+        } else if attribute_depth > 0 && matches!(rest[0], b'[' | b']') {
+            if rest[0] == b'[' {
+                attribute_depth += 1;
+            } else {
+                attribute_depth -= 1;
+            }
+            (1, false)
+        } else if is_ident_char(rest[0]) {
+            // Skip whole identifiers, so that string prefixes (e.g. `b` or `r#`) are only
+            // recognized at their start.
+            let len = rest
+                .iter()
+                .position(|&c| !is_ident_char(c))
+                .unwrap_or(rest.len());
+            (len, false)
+        } else if let Some(len) = char_literal_len(rest) {
+            (len, true)
+        } else {
+            (1, false)
+        };
+
+        if blank {
+            for c in &mut blanked[i..i + len] {
+                if *c != b'\n' {
+                    *c = b' ';
+                }
+            }
+        }
+
+        i += len;
+    }
+
+    blanked
+}
+
+/// Returns the lowercased names given as the value of a `with`, `*_with` or `crate` argument in
+/// the attributes of the given code, e.g. `humantime_serde` in
+/// `#[serde(with = "humantime_serde")]`: those name a module, which may be a crate.
+///
+/// The code must have gone through [`blank_comments_and_strings`] beforehand, so that the only
+/// string literals left are those of attributes.
+pub(super) fn attribute_module_names(code: &[u8]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    let mut i = 0;
+
+    while let Some(start) = code[i..].iter().position(|&c| c == b'"').map(|pos| i + pos) {
+        let len = string_literal_len(&code[start..]).unwrap_or(1);
+        i = start + len;
+
+        let value = &code[start + 1..(i - 1).max(start + 1)];
+        if value.is_empty() || !value.iter().all(|&c| is_ident_char(c)) {
+            continue;
+        }
+
+        // Look for `key =` before the string literal.
+        let before = code[..start].trim_ascii_end();
+        let Some(before) = before.strip_suffix(b"=") else {
+            continue;
+        };
+        let before = before.trim_ascii_end();
+        let key_start = before
+            .iter()
+            .rposition(|&c| !is_ident_char(c))
+            .map_or(0, |pos| pos + 1);
+        let key = &before[key_start..];
+        if key == b"with" || key == b"crate" || key.ends_with(b"_with") {
+            names.insert(String::from_utf8_lossy(value).to_lowercase());
+        }
+    }
+
+    names
+}
+
+fn is_ident_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || !c.is_ascii()
+}
+
+/// Returns the length of the attribute opening (`#[` or `#![`, possibly with whitespace in between)
+/// at the start of `code`, if there's one.
+fn attribute_start_len(code: &[u8]) -> Option<usize> {
+    if code.first() != Some(&b'#') {
+        return None;
+    }
+
+    let mut i = 1;
+    while code.get(i).is_some_and(u8::is_ascii_whitespace) {
+        i += 1;
+    }
+    if code.get(i) == Some(&b'!') {
+        i += 1;
+        while code.get(i).is_some_and(u8::is_ascii_whitespace) {
+            i += 1;
+        }
+    }
+    (code.get(i) == Some(&b'[')).then_some(i + 1)
+}
+
+/// Returns the length of the (possibly nested) block comment at the start of `code`.
+fn block_comment_len(code: &[u8]) -> usize {
+    let mut depth = 0;
+    let mut i = 0;
+
+    while i < code.len() {
+        if code[i..].starts_with(b"/*") {
+            depth += 1;
+            i += 2;
+        } else if code[i..].starts_with(b"*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += 1;
+        }
+    }
+
+    // Unterminated comment.
+    code.len()
+}
+
+/// Returns the length of the string literal at the start of `code`, if there's one.
+fn string_literal_len(code: &[u8]) -> Option<usize> {
+    let prefix_len = code
+        .iter()
+        .position(|&c| !c.is_ascii_alphabetic())
+        .unwrap_or(code.len());
+
+    match &code[..prefix_len] {
+        b"" | b"b" | b"c" if code.get(prefix_len) == Some(&b'"') => {
+            let mut i = prefix_len + 1;
+            while i < code.len() {
+                match code[i] {
+                    b'\\' => i += 2,
+                    b'"' => return Some(i + 1),
+                    _ => i += 1,
+                }
+            }
+            // Unterminated string.
+            Some(code.len())
+        }
+
+        b"r" | b"br" | b"cr" => {
+            let hashes = code[prefix_len..]
+                .iter()
+                .take_while(|&&c| c == b'#')
+                .count();
+            let start = prefix_len + hashes;
+            if code.get(start) != Some(&b'"') {
+                // A raw identifier (e.g. `r#type`), or a plain identifier.
+                return None;
+            }
+
+            let mut terminator = vec![b'"'];
+            terminator.resize(hashes + 1, b'#');
+
+            Some(
+                code[start + 1..]
+                    .windows(terminator.len())
+                    .position(|window| window == terminator)
+                    .map_or(code.len(), |pos| start + 1 + pos + terminator.len()),
+            )
+        }
+
+        _ => None,
+    }
+}
+
+/// Returns the length of the character literal at the start of `code`, if there's one (and not a
+/// lifetime or a label).
+fn char_literal_len(code: &[u8]) -> Option<usize> {
+    if code.first() != Some(&b'\'') {
+        return None;
+    }
+
+    if code.get(1) == Some(&b'\\') {
+        // Escaped character, e.g. `'\''` or `'\u{1F600}'`.
+        let end = code.get(3..)?.iter().position(|&c| c == b'\'')?;
+        return Some(3 + end + 1);
+    }
+
+    // Length of the UTF-8 encoded character.
+    let char_len = match *code.get(1)? {
+        c if c < 0x80 => 1,
+        c if c < 0xE0 => 2,
+        c if c < 0xF0 => 3,
+        _ => 4,
+    };
+
+    (code.get(1 + char_len) == Some(&b'\'')).then_some(char_len + 2)
+}
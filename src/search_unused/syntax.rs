@@ -0,0 +1,334 @@
+//! Syntax-aware detection of the crates used by Rust source code.
+//!
+//! Instead of matching regular expressions against the source code, this parses it into a syntax
+//! tree, and collects the roots of the paths it contains. Comments and string literals are thus
+//! never mistaken for code, and `use` trees can be nested arbitrarily deep.
+
+use std::collections::HashSet;
+
+use proc_macro2::{Delimiter, Spacing, TokenStream, TokenTree};
+use syn::visit::{self, Visit};
+
+/// Roots of the paths found in some Rust source code, lowercased.
+pub(super) struct CrateRoots {
+    /// Roots found in the code itself.
+    pub code: HashSet<String>,
+    /// Roots found in the Rust code blocks of doc comments (i.e. doc-tests).
+    pub doc_tests: HashSet<String>,
+}
+
+/// Returns the roots of the paths found in the given Rust source code, lowercased.
+///
+/// Roots are collected from `use` trees, `extern crate` items, and multi-segment (or `::`-prefixed)
+/// paths, including those of attributes and macro invocations. Since the arguments of macros and
+/// attributes can't be parsed in general, their tokens are scanned for paths instead. String
+/// literals in attribute arguments are scanned too, e.g. `#[serde(with = "humantime_serde")]`.
+///
+/// Doc-tests are only looked at if `match_doc_comments` is set.
+///
+/// Returns `None` if the source code can't even be tokenized.
+pub(super) fn crate_roots(content: &str, match_doc_comments: bool) -> Option<CrateRoots> {
+    let mut collector = RootCollector::default();
+
+    match syn::parse_file(content) {
+        Ok(file) => collector.visit_file(&file),
+        Err(err) => {
+            // Not a full Rust file (e.g. a snippet meant to be `include!`d): scan its tokens.
+            log::trace!("couldn't parse file, scanning tokens instead: {err}");
+            collector.scan_tokens(content.parse().ok()?);
+        }
+    }
+
+    let code = std::mem::take(&mut collector.roots);
+
+    if match_doc_comments {
+        for code in doc_tests(&std::mem::take(&mut collector.doc_lines)) {
+            // Doc-tests are implicitly wrapped in a `main` function, so parse them as a block.
+            match syn::parse_str::<syn::Block>(&format!("{{\n{code}\n}}")) {
+                Ok(block) => collector.visit_block(&block),
+                Err(_) => {
+                    if let Ok(tokens) = code.parse() {
+                        collector.scan_tokens(tokens);
+                    }
+                }
+            }
+        }
+    }
+
+    Some(CrateRoots {
+        code,
+        doc_tests: collector.roots,
+    })
+}
+
+#[derive(Default)]
+struct RootCollector {
+    /// Roots of all the paths seen so far, lowercased.
+    roots: HashSet<String>,
+    /// Lines of all the doc comments seen so far.
+    doc_lines: Vec<String>,
+}
+
+impl RootCollector {
+    fn add_root(&mut self, ident: &impl ToString) {
+        self.roots.insert(ident.to_string().to_lowercase());
+    }
+
+    fn add_use_tree_roots(&mut self, tree: &syn::UseTree) {
+        match tree {
+            syn::UseTree::Path(path) => self.add_root(&path.ident),
+            syn::UseTree::Name(name) => self.add_root(&name.ident),
+            syn::UseTree::Rename(rename) => self.add_root(&rename.ident),
+            syn::UseTree::Glob(_) => {}
+            syn::UseTree::Group(group) => {
+                for tree in &group.items {
+                    self.add_use_tree_roots(tree);
+                }
+            }
+        }
+    }
+
+    /// Looks for paths in a stream of tokens that couldn't be parsed.
+    fn scan_tokens(&mut self, tokens: TokenStream) {
+        let tokens = tokens.into_iter().collect::<Vec<_>>();
+
+        for (i, token) in tokens.iter().enumerate() {
+            match token {
+                TokenTree::Group(group) => {
+                    if group.delimiter() == Delimiter::Bracket
+                        && i > 0
+                        && (is_punct(&tokens[i - 1], '#') || is_punct(&tokens[i - 1], '!'))
+                    {
+                        // An attribute.
+                        self.scan_attribute_strings(group.stream());
+                    }
+                    self.scan_tokens(group.stream());
+                }
+
+                TokenTree::Ident(ident) if ident == "use" => {
+                    self.scan_use_tree(&tokens[i + 1..]);
+                }
+
+                TokenTree::Ident(ident)
+                    if ident == "crate" && i > 0 && is_ident(&tokens[i - 1], "extern") =>
+                {
+                    if let Some(TokenTree::Ident(name)) = tokens.get(i + 1) {
+                        self.add_root(name);
+                    }
+                }
+
+                TokenTree::Ident(ident) if is_path_separator(&tokens[i + 1..]) => {
+                    // Only keep the first segment of the path: it must not be preceded by `::`,
+                    // unless that's a leading `::` (e.g. `::log::info`, but not
+                    // `Vec::<u8>::new`).
+                    let preceded_by_separator = i >= 2 && is_path_separator(&tokens[i - 2..]);
+                    let is_root = !preceded_by_separator
+                        || i == 2
+                        || !(matches!(tokens[i - 3], TokenTree::Ident(_))
+                            || is_punct(&tokens[i - 3], '>'));
+                    if is_root {
+                        self.add_root(ident);
+                    }
+                }
+
+                _ => {}
+            }
+        }
+    }
+
+    /// Looks for paths in the string literals of attribute arguments, e.g.
+    /// `deserialize_with = "humantime_serde::deserialize"`.
+    ///
+    /// Single-segment paths are only considered when they name a module or a crate, i.e. as the
+    /// value of a `with`, `*_with` or `crate` argument.
+    fn scan_attribute_strings(&mut self, tokens: TokenStream) {
+        let tokens = tokens.into_iter().collect::<Vec<_>>();
+
+        for (i, token) in tokens.iter().enumerate() {
+            match token {
+                TokenTree::Group(group) => self.scan_attribute_strings(group.stream()),
+
+                TokenTree::Literal(lit) => {
+                    let Ok(lit) = syn::parse2::<syn::LitStr>(TokenTree::from(lit.clone()).into())
+                    else {
+                        continue;
+                    };
+                    let Ok(path) = lit.parse::<syn::Path>() else {
+                        continue;
+                    };
+                    let names_module = i >= 2
+                        && is_punct(&tokens[i - 1], '=')
+                        && matches!(&tokens[i - 2], TokenTree::Ident(key)
+                            if key == "with" || key == "crate" || key.to_string().ends_with("_with"));
+                    if (path.segments.len() > 1 || path.leading_colon.is_some() || names_module)
+                        && let Some(first) = path.segments.first()
+                    {
+                        self.add_root(&first.ident);
+                    }
+                }
+
+                _ => {}
+            }
+        }
+    }
+
+    /// Looks for the roots of the `use` tree at the beginning of the given tokens.
+    fn scan_use_tree(&mut self, tokens: &[TokenTree]) {
+        let tokens = if is_path_separator(tokens) {
+            &tokens[2..]
+        } else {
+            tokens
+        };
+
+        match tokens.first() {
+            Some(TokenTree::Ident(ident)) => self.add_root(ident),
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => {
+                let items = group.stream().into_iter().collect::<Vec<_>>();
+                for item in items.split(|token| is_punct(token, ',')) {
+                    self.scan_use_tree(item);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for RootCollector {
+    fn visit_item(&mut self, item: &'ast syn::Item) {
+        // syn falls back to verbatim tokens for some syntax it doesn't represent, e.g. `use`
+        // groups containing `::`-prefixed paths.
+        if let syn::Item::Verbatim(tokens) = item {
+            self.scan_tokens(tokens.clone());
+        }
+        visit::visit_item(self, item);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast syn::Expr) {
+        if let syn::Expr::Verbatim(tokens) = expr {
+            self.scan_tokens(tokens.clone());
+        }
+        visit::visit_expr(self, expr);
+    }
+
+    fn visit_item_use(&mut self, item: &'ast syn::ItemUse) {
+        // Don't visit the `use` tree itself: only its roots matter, not its inner paths.
+        for attr in &item.attrs {
+            self.visit_attribute(attr);
+        }
+        self.add_use_tree_roots(&item.tree);
+    }
+
+    fn visit_item_extern_crate(&mut self, item: &'ast syn::ItemExternCrate) {
+        self.add_root(&item.ident);
+        visit::visit_item_extern_crate(self, item);
+    }
+
+    fn visit_path(&mut self, path: &'ast syn::Path) {
+        // A single-segment path may be a local variable or type; only consider it when it's
+        // explicitly a crate, with a leading `::`.
+        if (path.segments.len() > 1 || path.leading_colon.is_some())
+            && let Some(first) = path.segments.first()
+        {
+            self.add_root(&first.ident);
+        }
+        visit::visit_path(self, path);
+    }
+
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        self.scan_tokens(mac.tokens.clone());
+        visit::visit_macro(self, mac);
+    }
+
+    fn visit_meta_list(&mut self, list: &'ast syn::MetaList) {
+        // e.g. `#[derive(serde::Serialize)]`.
+        self.scan_tokens(list.tokens.clone());
+        self.scan_attribute_strings(list.tokens.clone());
+        visit::visit_meta_list(self, list);
+    }
+
+    fn visit_attribute(&mut self, attr: &'ast syn::Attribute) {
+        if let syn::Meta::NameValue(meta) = &attr.meta
+            && meta.path.is_ident("doc")
+            && let syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(doc),
+                ..
+            }) = &meta.value
+        {
+            self.doc_lines
+                .extend(doc.value().lines().map(ToOwned::to_owned));
+        }
+        visit::visit_attribute(self, attr);
+    }
+}
+
+fn is_ident(token: &TokenTree, name: &str) -> bool {
+    matches!(token, TokenTree::Ident(ident) if ident == name)
+}
+
+fn is_punct(token: &TokenTree, ch: char) -> bool {
+    matches!(token, TokenTree::Punct(punct) if punct.as_char() == ch)
+}
+
+/// Do the given tokens start with a `::` path separator?
+fn is_path_separator(tokens: &[TokenTree]) -> bool {
+    match tokens {
+        [TokenTree::Punct(first), TokenTree::Punct(second), ..] => {
+            first.as_char() == ':' && first.spacing() == Spacing::Joint && second.as_char() == ':'
+        }
+        _ => false,
+    }
+}
+
+/// Extracts the Rust code blocks from the given doc comment lines, with hidden lines (starting
+/// with `# `) included.
+fn doc_tests(doc_lines: &[String]) -> Vec<String> {
+    enum State<'a> {
+        /// Outside of any code block.
+        Text,
+        /// Within a Rust code block, with the lines seen so far.
+        RustCode(Vec<&'a str>),
+        /// Within a code block in another language.
+        OtherCode,
+    }
+
+    let mut code_blocks = Vec::new();
+    let mut state = State::Text;
+
+    for line in doc_lines {
+        let line = line.trim_start();
+
+        if let Some(info) = line.strip_prefix("```") {
+            state = match state {
+                State::Text if is_rust_code_block(info) => State::RustCode(Vec::new()),
+                State::Text => State::OtherCode,
+                State::RustCode(lines) => {
+                    code_blocks.push(lines.join("\n"));
+                    State::Text
+                }
+                State::OtherCode => State::Text,
+            };
+            continue;
+        }
+
+        if let State::RustCode(lines) = &mut state {
+            let line = match line.strip_prefix('#') {
+                Some(hidden) if hidden.is_empty() || hidden.starts_with(' ') => hidden,
+                _ => line,
+            };
+            lines.push(line);
+        }
+    }
+
+    code_blocks
+}
+
+/// Is the code block with the given info string (what follows the opening fence) a doc-test?
+fn is_rust_code_block(info: &str) -> bool {
+    info.split(|c: char| c == ',' || c.is_whitespace())
+        .all(|attr| {
+            matches!(
+                attr,
+                "" | "rust" | "ignore" | "should_panic" | "no_run" | "compile_fail"
+            ) || attr.starts_with("edition")
+        })
+}